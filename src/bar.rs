@@ -1,10 +1,10 @@
-use chrono::Local;
 use cosmic::cctk;
 use cosmic::cctk::sctk::shell::wlr_layer::Anchor;
 use cosmic::iced::alignment::Vertical;
-use cosmic::iced::{self, Border, Length, Padding, Subscription, Task, Theme, time, window};
+use cosmic::iced::{self, Border, Color, Length, Padding, Subscription, Task, Theme, window};
 use cosmic::iced_runtime::platform_specific::wayland::layer_surface::IcedOutput;
 use cosmic::iced_widget::row;
+use cosmic::widget::Space;
 use hyprland::dispatch::DispatchType;
 use hyprland::dispatch::{Dispatch, WorkspaceIdentifierWithSpecial};
 use hyprland::shared::HyprData;
@@ -13,7 +13,49 @@ use iced::border::radius;
 use iced::platform_specific::shell::commands::layer_surface::get_layer_surface;
 use iced::widget::{container, text};
 
-use crate::{ShellMessage, window::Window};
+use crate::{ShellMessage, power, window::Window};
+use module::Module;
+use popup::Popup;
+
+pub mod brightness;
+pub mod caffeine;
+pub mod clock;
+pub mod custom;
+pub mod hwmon;
+mod module;
+pub mod popup;
+pub mod sysinfo;
+mod timer;
+
+// How alarming a reading is, used to pick the text color of a module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl Level {
+    pub fn from_thresholds(value: f32, warning: f32, critical: f32) -> Self {
+        if value >= critical {
+            Level::Critical
+        } else if value >= warning {
+            Level::Warning
+        } else {
+            Level::Normal
+        }
+    }
+}
+
+pub fn level_style(level: Level) -> impl Fn(&Theme) -> text::Style {
+    move |theme: &Theme| text::Style {
+        color: match level {
+            Level::Normal => None,
+            Level::Warning => Some(Color::from_rgb8(0xe5, 0x9e, 0x1d)),
+            Level::Critical => Some(theme.extended_palette().danger.base.color),
+        },
+    }
+}
 
 // This enum is for identifying workspaces that also includes the special workspace
 // Because hyprland-rs' doesn't work because of lifetime stuff
@@ -43,18 +85,73 @@ pub enum WorkspaceIdentifier {
 pub struct Bar {
     pub count: u32,
     pub active_workspace: i32,
+    // Sorted by id, refreshed on workspace events instead of on every redraw
+    pub workspaces: Vec<hyprland::data::Workspace>,
     pub id: window::Id,
+    pub popups: popup::Popups,
+    pub sysinfo: sysinfo::Sysinfo,
+    pub hwmon: hwmon::Hwmon,
+    pub brightness: brightness::Brightness,
+    pub clock: clock::Clock,
+    pub caffeine: caffeine::Caffeine,
+    pub custom: custom::Custom,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     ShellMessage(Box<ShellMessage>),
-    TimeTick(iced::time::Instant),
     ButtonPressed,
     OpenBlueman,
     HyprlandEvent(hyprland::event_listener::Event),
     HyprlandError,
     SetWorkspace(WorkspaceIdentifier),
+    Popups(popup::Message),
+    Sysinfo(sysinfo::Message),
+    Hwmon(hwmon::Message),
+    Brightness(brightness::Message),
+    Clock(clock::Message),
+    Caffeine(caffeine::Message),
+    Custom(custom::Message),
+}
+
+fn get_workspaces() -> Vec<hyprland::data::Workspace> {
+    let mut workspaces = match hyprland::data::Workspaces::get() {
+        Ok(workspaces) => workspaces.to_vec(),
+        Err(error) => {
+            dbg!(error);
+            Vec::new()
+        }
+    };
+    workspaces.sort_by_key(|workspace| workspace.id);
+    workspaces
+}
+
+impl Bar {
+    // The popup's content, or the transparent backdrop behind it
+    pub fn popup_view(self: &Self, id: window::Id) -> Option<cosmic::iced::Element<'_, Message>> {
+        if self.popups.is_backdrop(id) {
+            return Some(Space::new(Length::Fill, Length::Fill).into());
+        }
+
+        let popup = self.popups.popup(id)?;
+        Some(
+            container(match popup {
+                Popup::Sensors => self.hwmon.popup_view().map(Message::Hwmon),
+                Popup::Calendar => self.clock.popup_view().map(Message::Clock),
+            })
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(|theme: &Theme| container::Style {
+                border: Border {
+                    radius: radius(10),
+                    ..Default::default()
+                },
+                background: Some(theme.extended_palette().background.weak.color.into()),
+                ..Default::default()
+            })
+            .into(),
+        )
+    }
 }
 
 impl Window for Bar {
@@ -78,15 +175,38 @@ impl Window for Bar {
             },
         );
 
+        let (sysinfo, sysinfo_task) = sysinfo::Sysinfo::new();
+        let (hwmon, hwmon_task) = hwmon::Hwmon::new();
+        let (brightness, brightness_task) = brightness::Brightness::new();
+        let (clock, clock_task) = clock::Clock::new();
+        let (caffeine, caffeine_task) = caffeine::Caffeine::new();
+        let (custom, custom_task) = custom::Custom::new();
+
         (
             Self {
                 count: 0,
                 active_workspace: hyprland::data::Workspace::get_active()
                     .expect("Failed to get hyprland workspace")
                     .id,
+                workspaces: get_workspaces(),
                 id: id,
+                popups: popup::Popups::default(),
+                sysinfo,
+                hwmon,
+                brightness,
+                clock,
+                caffeine,
+                custom,
             },
-            layer_shell_task,
+            Task::batch(vec![
+                layer_shell_task,
+                sysinfo_task.map(Message::Sysinfo),
+                hwmon_task.map(Message::Hwmon),
+                brightness_task.map(Message::Brightness),
+                clock_task.map(Message::Clock),
+                caffeine_task.map(Message::Caffeine),
+                custom_task.map(Message::Custom),
+            ]),
         )
     }
 
@@ -94,29 +214,31 @@ impl Window for Bar {
         container(
             row![
                 text(self.count),
-                iced::widget::row({
-                    let mut workspaces = hyprland::data::Workspaces::get().unwrap().to_vec();
-
-                    workspaces.sort_by_key(|workspace| workspace.id);
-                    workspaces.into_iter().map(|workspace| {
-                        let name = workspace.name;
-                        iced::widget::button(text(name.clone()))
-                            .on_press_maybe(if workspace.id != self.active_workspace {
-                                Some(Message::SetWorkspace(WorkspaceIdentifier::Name(name)))
-                            } else {
-                                None
-                            })
-                            .into()
-                    })
-                }),
+                iced::widget::row(self.workspaces.iter().map(|workspace| {
+                    let name = &workspace.name;
+                    iced::widget::button(text(name))
+                        .on_press_maybe(if workspace.id != self.active_workspace {
+                            Some(Message::SetWorkspace(WorkspaceIdentifier::Name(
+                                name.clone(),
+                            )))
+                        } else {
+                            None
+                        })
+                        .into()
+                })),
                 text("Hello, World! I'm a bad status bar!")
                     .width(Length::Fill)
                     .center(),
+                self.custom.view().map(Message::Custom),
+                self.sysinfo.view().map(Message::Sysinfo),
+                container(self.hwmon.view().map(Message::Hwmon)).id(Popup::Sensors.anchor()),
+                self.brightness.view().map(Message::Brightness),
+                self.caffeine.view().map(Message::Caffeine),
                 iced::widget::button("").on_press(Message::OpenBlueman),
-                text(format!(
-                    "{}",
-                    Local::now().format("%A, %B %e, %Y  %H:%M:%S")
-                )),
+                container(self.clock.view().map(Message::Clock)).id(Popup::Calendar.anchor()),
+                iced::widget::button("⏻").on_press(Message::ShellMessage(Box::new(
+                    ShellMessage::PowerMessage(power::Message::Open)
+                ))),
             ]
             .align_y(Vertical::Center)
             .spacing(10)
@@ -133,13 +255,13 @@ impl Window for Bar {
             ..Default::default()
         })
         .padding(Padding::from([0, 0]))
+        .id(popup::bar_anchor())
         .into()
     }
 
     fn update(self: &mut Self, message: Self::Message) -> cosmic::Task<Self::Message> {
         use Message::*;
         match message {
-            TimeTick(_) => Task::none(),
             ButtonPressed => {
                 self.count += 1;
                 Task::none()
@@ -167,23 +289,57 @@ impl Window for Bar {
             HyprlandEvent(event) => match event {
                 hyprland::event_listener::Event::WorkspaceChanged(data) => {
                     self.active_workspace = data.id;
+                    self.workspaces = get_workspaces();
+                    Task::done(Caffeine(caffeine::Message::CheckFullscreen))
+                }
+                hyprland::event_listener::Event::FullscreenStateChanged(_)
+                | hyprland::event_listener::Event::WindowClosed(_) => {
+                    Task::done(Caffeine(caffeine::Message::CheckFullscreen))
+                }
+                hyprland::event_listener::Event::WorkspaceAdded(_)
+                | hyprland::event_listener::Event::WorkspaceDeleted(_)
+                | hyprland::event_listener::Event::WorkspaceMoved(_)
+                | hyprland::event_listener::Event::WorkspaceRenamed(_) => {
+                    self.workspaces = get_workspaces();
                     Task::none()
                 }
                 _ => Task::none(),
             },
+            Popups(message) => self.popups.update(message).map(Message::Popups),
+            Sysinfo(message) => self.sysinfo.update(message).map(Message::Sysinfo),
+            Hwmon(hwmon::Message::TogglePopup) => {
+                Task::done(Popups(popup::Message::Toggle(Popup::Sensors)))
+            }
+            Hwmon(message) => self.hwmon.update(message).map(Message::Hwmon),
+            Brightness(message) => self.brightness.update(message).map(Message::Brightness),
+            Clock(clock::Message::TogglePopup) => Task::batch(vec![
+                self.clock
+                    .update(clock::Message::TogglePopup)
+                    .map(Message::Clock),
+                Task::done(Popups(popup::Message::Toggle(Popup::Calendar))),
+            ]),
+            Clock(message) => self.clock.update(message).map(Message::Clock),
+            Caffeine(message) => self.caffeine.update(message).map(Message::Caffeine),
+            Custom(message) => self.custom.update(message).map(Message::Custom),
             ShellMessage(_) => Task::none(),
         }
     }
 
     fn subscription(self: &Self) -> cosmic::iced::Subscription<Self::Message> {
         Subscription::batch([
-            time::every(std::time::Duration::from_millis(100)).map(Message::TimeTick),
             Subscription::run(|| hyprland::event_listener::EventStream::new()).map(|hyprevent| {
                 match hyprevent {
                     Ok(result) => Message::HyprlandEvent(result),
                     Err(_) => Message::HyprlandError,
                 }
             }),
+            self.sysinfo.subscription().map(Message::Sysinfo),
+            self.hwmon.subscription().map(Message::Hwmon),
+            self.brightness.subscription().map(Message::Brightness),
+            self.clock.subscription().map(Message::Clock),
+            self.caffeine.subscription().map(Message::Caffeine),
+            self.custom.subscription().map(Message::Custom),
+            self.popups.subscription().map(Message::Popups),
         ])
     }
 }
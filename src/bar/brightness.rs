@@ -0,0 +1,437 @@
+use std::path::{Path, PathBuf};
+
+use cosmic::iced::futures::{SinkExt, StreamExt};
+use cosmic::iced::mouse::ScrollDelta;
+use cosmic::iced::{self, Element, Subscription, Task};
+use cosmic::iced_widget::row;
+use iced::widget::{mouse_area, text};
+use inotify::{Inotify, WatchMask};
+use serde::Deserialize;
+
+use super::module::Module;
+use crate::{config, dbus, logind};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Name of the /sys/class/backlight device to show, the first one if unset
+    pub backlight: Option<String>,
+    // Also show keyboard backlights from /sys/class/leds
+    pub keyboard: bool,
+    // Percentage added or removed per scroll step
+    pub step: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            backlight: None,
+            keyboard: true,
+            step: 5.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    Backlight,
+    Keyboard,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    pub kind: DeviceKind,
+    // Directory name under /sys/class/<subsystem>, which is what logind expects
+    pub name: String,
+    pub path: PathBuf,
+    pub brightness: u32,
+    pub max_brightness: u32,
+}
+
+impl Device {
+    fn read(kind: DeviceKind, path: PathBuf) -> Option<Self> {
+        let read = |name: &str| -> Option<u32> {
+            std::fs::read_to_string(path.join(name))
+                .ok()?
+                .trim()
+                .parse()
+                .ok()
+        };
+        // actual_brightness is what the hardware reports, brightness only what was last requested
+        let brightness = match kind {
+            DeviceKind::Backlight => read("actual_brightness").or_else(|| read("brightness"))?,
+            DeviceKind::Keyboard => read("brightness")?,
+        };
+        Some(Self {
+            kind,
+            name: path.file_name()?.to_string_lossy().to_string(),
+            max_brightness: read("max_brightness")?,
+            brightness,
+            path,
+        })
+    }
+
+    pub fn subsystem(&self) -> &'static str {
+        match self.kind {
+            DeviceKind::Backlight => "backlight",
+            DeviceKind::Keyboard => "leds",
+        }
+    }
+
+    // The brightness after moving `steps` steps of `step` percent, positive is brighter
+    pub fn adjusted(&self, steps: i32, step: f32) -> u32 {
+        let step = ((self.max_brightness as f32 * step / 100.0).round() as i64).max(1);
+        // Never turn the screen off entirely by scrolling
+        let min = match self.kind {
+            DeviceKind::Backlight => 1,
+            DeviceKind::Keyboard => 0,
+        };
+        let max = self.max_brightness as i64;
+        (self.brightness as i64 + step * steps as i64).clamp(min.min(max), max) as u32
+    }
+
+    pub fn percent(&self) -> f32 {
+        if self.max_brightness == 0 {
+            return 0.0;
+        }
+        self.brightness as f32 / self.max_brightness as f32 * 100.0
+    }
+
+    // Files whose modification means the brightness changed behind our back. The kernel
+    // updates actual_brightness without going through the VFS, so inotify never sees it change,
+    // but logind and brightnessctl set the backlight by writing brightness.
+    pub fn watched_files(&self) -> Vec<PathBuf> {
+        match self.kind {
+            DeviceKind::Backlight => vec![self.path.join("brightness")],
+            DeviceKind::Keyboard => vec![
+                self.path.join("brightness"),
+                self.path.join("brightness_hw_changed"),
+            ],
+        }
+    }
+}
+
+// Finds the backlight and keyboard backlight devices under a /sys/class style directory
+pub fn read_devices(sys_class: &Path, config: &Config) -> Vec<Device> {
+    let list = |subsystem: &str| -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = match std::fs::read_dir(sys_class.join(subsystem)) {
+            Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+            Err(_) => Vec::new(),
+        };
+        paths.sort();
+        paths
+    };
+
+    let mut devices = Vec::new();
+
+    let backlight = list("backlight")
+        .into_iter()
+        .find(|path| match &config.backlight {
+            Some(name) => path
+                .file_name()
+                .is_some_and(|file_name| file_name == name.as_str()),
+            None => true,
+        });
+    devices.extend(backlight.and_then(|path| Device::read(DeviceKind::Backlight, path)));
+
+    if config.keyboard {
+        devices.extend(
+            list("leds")
+                .into_iter()
+                .filter(|path| {
+                    path.file_name()
+                        .is_some_and(|name| name.to_string_lossy().ends_with("kbd_backlight"))
+                })
+                .filter_map(|path| Device::read(DeviceKind::Keyboard, path)),
+        );
+    }
+
+    devices
+}
+
+#[derive(Debug)]
+pub struct Brightness {
+    pub devices: Vec<Device>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    // Steps to move the device at the index by, positive is brighter
+    Adjust(usize, i32),
+    Changed,
+    BrightnessSet(Result<(), String>),
+}
+
+async fn set_brightness(subsystem: &str, name: &str, brightness: u32) -> zbus::Result<()> {
+    let session = logind::SessionProxy::new(dbus::system_bus().await?).await?;
+    session.set_brightness(subsystem, name, brightness).await
+}
+
+impl Module for Brightness {
+    type Message = Message;
+
+    fn new() -> (Self, Task<Self::Message>) {
+        (
+            Self {
+                devices: read_devices(Path::new("/sys/class"), &config::get().brightness),
+            },
+            Task::none(),
+        )
+    }
+
+    fn view(self: &Self) -> Element<'_, Self::Message> {
+        row(self.devices.iter().enumerate().map(|(index, device)| {
+            let icon = match device.kind {
+                DeviceKind::Backlight => "☀",
+                DeviceKind::Keyboard => "⌨",
+            };
+            mouse_area(text(format!("{} {:.0}%", icon, device.percent())))
+                .on_scroll(move |delta| {
+                    let y = match delta {
+                        ScrollDelta::Lines { y, .. } => y,
+                        ScrollDelta::Pixels { y, .. } => y,
+                    };
+                    Message::Adjust(index, if y > 0.0 { 1 } else { -1 })
+                })
+                .into()
+        }))
+        .spacing(6)
+        .into()
+    }
+
+    fn update(self: &mut Self, message: Self::Message) -> Task<Self::Message> {
+        use Message::*;
+        match message {
+            Adjust(index, steps) => {
+                let Some(device) = self.devices.get_mut(index) else {
+                    return Task::none();
+                };
+
+                let brightness = device.adjusted(steps, config::get().brightness.step);
+                if brightness == device.brightness {
+                    return Task::none();
+                }
+                device.brightness = brightness;
+
+                let subsystem = device.subsystem();
+                let name = device.name.clone();
+                Task::perform(
+                    async move {
+                        set_brightness(subsystem, &name, brightness)
+                            .await
+                            .map_err(|error| error.to_string())
+                    },
+                    BrightnessSet,
+                )
+            }
+            Changed => {
+                self.devices = read_devices(Path::new("/sys/class"), &config::get().brightness);
+                Task::none()
+            }
+            BrightnessSet(Ok(())) => Task::none(),
+            BrightnessSet(Err(error)) => {
+                dbg!(error);
+                // Show the real value again instead of the one we failed to set
+                Task::done(Changed)
+            }
+        }
+    }
+
+    fn subscription(self: &Self) -> iced::Subscription<Self::Message> {
+        // Keyed on the files, so picking other devices after a config reload restarts it
+        let paths: Vec<PathBuf> = self
+            .devices
+            .iter()
+            .flat_map(|device| device.watched_files())
+            .collect();
+        Subscription::run_with_id(
+            ("brightness", paths.clone()),
+            iced::stream::channel(10, async move |mut output| {
+                let inotify = match Inotify::init() {
+                    Ok(inotify) => inotify,
+                    Err(error) => {
+                        dbg!(error);
+                        return;
+                    }
+                };
+                for path in paths {
+                    if path.exists() {
+                        let _ = inotify.watches().add(&path, WatchMask::MODIFY);
+                    }
+                }
+
+                let mut events = match inotify.into_event_stream([0u8; 1024]) {
+                    Ok(events) => events,
+                    Err(error) => {
+                        dbg!(error);
+                        return;
+                    }
+                };
+
+                while events.next().await.is_some() {
+                    let _ = output.send(Message::Changed).await;
+                }
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A /sys/class lookalike in a temporary directory, removed when dropped
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let root =
+                std::env::temp_dir().join(format!("brightness-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(&root).unwrap();
+            Self(root)
+        }
+
+        fn device(&self, subsystem: &str, name: &str, files: &[(&str, &str)]) -> &Self {
+            let dir = self.0.join(subsystem).join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            for (file, contents) in files {
+                std::fs::write(dir.join(file), format!("{}\n", contents)).unwrap();
+            }
+            self
+        }
+
+        fn devices(&self, config: &Config) -> Vec<Device> {
+            read_devices(&self.0, config)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn laptop(name: &str) -> Fixture {
+        let fixture = Fixture::new(name);
+        fixture
+            .device(
+                "backlight",
+                "intel_backlight",
+                &[
+                    ("brightness", "9000"),
+                    ("actual_brightness", "12000"),
+                    ("max_brightness", "24000"),
+                ],
+            )
+            .device(
+                "backlight",
+                "acpi_video0",
+                &[("brightness", "5"), ("max_brightness", "10")],
+            )
+            .device(
+                "leds",
+                "tpacpi::kbd_backlight",
+                &[("brightness", "1"), ("max_brightness", "2")],
+            )
+            .device(
+                "leds",
+                "input3::capslock",
+                &[("brightness", "0"), ("max_brightness", "1")],
+            );
+        fixture
+    }
+
+    fn device(kind: DeviceKind, brightness: u32, max_brightness: u32) -> Device {
+        Device {
+            kind,
+            name: "test".to_string(),
+            path: PathBuf::from("/sys/class/backlight/test"),
+            brightness,
+            max_brightness,
+        }
+    }
+
+    #[test]
+    fn reads_first_backlight_and_keyboards() {
+        let fixture = laptop("default");
+        let devices = fixture.devices(&Config::default());
+        assert_eq!(devices.len(), 2);
+
+        // Sorted by name, and with the brightness the hardware reports
+        assert_eq!(devices[0].kind, DeviceKind::Backlight);
+        assert_eq!(devices[0].name, "acpi_video0");
+        assert_eq!(devices[0].brightness, 5);
+        assert_eq!(devices[0].percent(), 50.0);
+        assert_eq!(devices[0].subsystem(), "backlight");
+
+        assert_eq!(devices[1].kind, DeviceKind::Keyboard);
+        assert_eq!(devices[1].name, "tpacpi::kbd_backlight");
+        assert_eq!(devices[1].subsystem(), "leds");
+    }
+
+    #[test]
+    fn picks_the_configured_backlight() {
+        let fixture = laptop("configured");
+        let config = Config {
+            backlight: Some("intel_backlight".to_string()),
+            keyboard: false,
+            ..Config::default()
+        };
+        let devices = fixture.devices(&config);
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].name, "intel_backlight");
+        assert_eq!(devices[0].brightness, 12000);
+        assert_eq!(devices[0].max_brightness, 24000);
+        assert_eq!(
+            devices[0].watched_files(),
+            [fixture.0.join("backlight/intel_backlight/brightness")]
+        );
+
+        let config = Config {
+            backlight: Some("missing".to_string()),
+            ..config
+        };
+        assert!(fixture.devices(&config).is_empty());
+    }
+
+    #[test]
+    fn skips_unreadable_devices() {
+        let fixture = Fixture::new("broken");
+        fixture
+            .device("backlight", "no_max", &[("brightness", "5")])
+            .device(
+                "leds",
+                "garbage::kbd_backlight",
+                &[("brightness", "bright"), ("max_brightness", "3")],
+            );
+        assert!(fixture.devices(&Config::default()).is_empty());
+        assert!(Fixture::new("empty").devices(&Config::default()).is_empty());
+    }
+
+    #[test]
+    fn adjusts_by_percent_steps() {
+        let backlight = device(DeviceKind::Backlight, 12000, 24000);
+        assert_eq!(backlight.adjusted(1, 5.0), 13200);
+        assert_eq!(backlight.adjusted(-2, 5.0), 9600);
+        // Devices with few levels still move by at least one
+        let keyboard = device(DeviceKind::Keyboard, 1, 2);
+        assert_eq!(keyboard.adjusted(1, 5.0), 2);
+        assert_eq!(keyboard.adjusted(-1, 5.0), 0);
+    }
+
+    #[test]
+    fn clamps_to_the_device_range() {
+        let backlight = device(DeviceKind::Backlight, 23000, 24000);
+        assert_eq!(backlight.adjusted(3, 5.0), 24000);
+        // Scrolling never turns the backlight off, but keyboards may go dark
+        let backlight = device(DeviceKind::Backlight, 1000, 24000);
+        assert_eq!(backlight.adjusted(-5, 5.0), 1);
+        let keyboard = device(DeviceKind::Keyboard, 1, 3);
+        assert_eq!(keyboard.adjusted(-5, 5.0), 0);
+        // Nor does a broken max_brightness of 0 underflow
+        let broken = device(DeviceKind::Backlight, 0, 0);
+        assert_eq!(broken.adjusted(1, 5.0), 0);
+        assert_eq!(broken.adjusted(-1, 5.0), 0);
+    }
+}
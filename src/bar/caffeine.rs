@@ -0,0 +1,189 @@
+use cosmic::iced::{self, Element, Subscription, Task, Theme};
+use hyprland::prelude::*;
+use iced::widget::{mouse_area, text};
+use serde::Deserialize;
+use zbus::proxy;
+
+use super::module::Module;
+use crate::{config, dbus};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Inhibit idling while any window is fullscreen, even if the module is toggled off
+    pub auto_fullscreen: bool,
+}
+
+// Honoured by hypridle and most other idle daemons. The inhibitor lives as long as the
+// connection that created it, which is why this uses the shared session bus connection.
+#[proxy(
+    interface = "org.freedesktop.ScreenSaver",
+    default_service = "org.freedesktop.ScreenSaver",
+    default_path = "/org/freedesktop/ScreenSaver"
+)]
+trait ScreenSaver {
+    #[zbus(name = "Inhibit")]
+    fn inhibit(&self, application_name: &str, reason: &str) -> zbus::Result<u32>;
+    #[zbus(name = "UnInhibit")]
+    fn uninhibit(&self, cookie: u32) -> zbus::Result<()>;
+}
+
+async fn inhibit() -> Result<u32, String> {
+    async {
+        let screensaver = ScreenSaverProxy::new(dbus::session_bus().await?).await?;
+        screensaver
+            .inhibit("my-shell", "Idle inhibitor enabled")
+            .await
+    }
+    .await
+    .map_err(|error: zbus::Error| error.to_string())
+}
+
+async fn uninhibit(cookie: u32) -> Result<(), String> {
+    async {
+        let screensaver = ScreenSaverProxy::new(dbus::session_bus().await?).await?;
+        screensaver.uninhibit(cookie).await
+    }
+    .await
+    .map_err(|error: zbus::Error| error.to_string())
+}
+
+fn any_fullscreen() -> bool {
+    match hyprland::data::Workspaces::get() {
+        Ok(workspaces) => workspaces
+            .to_vec()
+            .iter()
+            .any(|workspace| workspace.fullscreen),
+        Err(error) => {
+            dbg!(error);
+            false
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Caffeine {
+    // Toggled by clicking the module
+    pub manual: bool,
+    pub fullscreen: bool,
+    // Whether an inhibitor was asked for, which can be ahead of `cookie` while a call is pending
+    pub requested: bool,
+    // Whether an Inhibit call is in flight, so toggling back on doesn't take a second inhibitor
+    pub pending: bool,
+    pub cookie: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Toggle,
+    // Sent by the bar on Hyprland events that can change whether something is fullscreen
+    CheckFullscreen,
+    Inhibited(Result<u32, String>),
+    Uninhibited(Result<(), String>),
+}
+
+impl Caffeine {
+    pub fn active(&self) -> bool {
+        self.manual || (config::get().caffeine.auto_fullscreen && self.fullscreen)
+    }
+
+    // Takes or releases the inhibitor to match active()
+    fn sync(&mut self) -> Task<Message> {
+        let wanted = self.active();
+        if wanted == self.requested {
+            return Task::none();
+        }
+        self.requested = wanted;
+
+        if wanted {
+            if self.pending || self.cookie.is_some() {
+                // The pending cookie is kept once it arrives
+                return Task::none();
+            }
+            self.pending = true;
+            Task::perform(inhibit(), Message::Inhibited)
+        } else if let Some(cookie) = self.cookie.take() {
+            Task::perform(uninhibit(cookie), Message::Uninhibited)
+        } else {
+            // Inhibit is still pending, it gets released once the cookie arrives
+            Task::none()
+        }
+    }
+}
+
+impl Module for Caffeine {
+    type Message = Message;
+
+    fn new() -> (Self, Task<Self::Message>) {
+        (
+            Self {
+                manual: false,
+                fullscreen: false,
+                requested: false,
+                pending: false,
+                cookie: None,
+            },
+            Task::done(Message::CheckFullscreen),
+        )
+    }
+
+    fn view(self: &Self) -> Element<'_, Self::Message> {
+        let active = self.active();
+        let label = if active && !self.manual {
+            "☕ auto"
+        } else {
+            "☕"
+        };
+        mouse_area(text(label).style(move |theme: &Theme| text::Style {
+            color: if active {
+                Some(theme.extended_palette().primary.base.color)
+            } else {
+                Some(theme.extended_palette().background.strong.color)
+            },
+        }))
+        .on_press(Message::Toggle)
+        .into()
+    }
+
+    fn update(self: &mut Self, message: Self::Message) -> Task<Self::Message> {
+        use Message::*;
+        match message {
+            Toggle => {
+                self.manual = !self.manual;
+                self.sync()
+            }
+            CheckFullscreen => {
+                if !config::get().caffeine.auto_fullscreen {
+                    return Task::none();
+                }
+                self.fullscreen = any_fullscreen();
+                self.sync()
+            }
+            Inhibited(Ok(cookie)) => {
+                self.pending = false;
+                if !self.requested {
+                    // Toggled off while the call was in flight
+                    return Task::perform(uninhibit(cookie), Uninhibited);
+                }
+                self.cookie = Some(cookie);
+                Task::none()
+            }
+            Inhibited(Err(error)) => {
+                dbg!(error);
+                self.pending = false;
+                self.manual = false;
+                self.requested = false;
+                Task::none()
+            }
+            Uninhibited(Ok(())) => Task::none(),
+            Uninhibited(Err(error)) => {
+                dbg!(error);
+                Task::none()
+            }
+        }
+    }
+
+    fn subscription(self: &Self) -> iced::Subscription<Self::Message> {
+        Subscription::none()
+    }
+}
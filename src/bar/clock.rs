@@ -0,0 +1,494 @@
+use std::time::{Duration, SystemTime};
+
+use chrono::format::{Fixed, Item, Numeric, StrftimeItems};
+use chrono::{DateTime, Datelike, Days, Local, Locale, Months, NaiveDate, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use cosmic::iced::{self, Element, Length, Task, Theme};
+use cosmic::iced_widget::{column, row};
+use iced::widget::{button, container, mouse_area, text, tooltip};
+use serde::Deserialize;
+
+use super::module::Module;
+use super::timer;
+use crate::config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    Monday,
+    Sunday,
+}
+
+impl WeekStart {
+    fn weekday(self) -> Weekday {
+        match self {
+            WeekStart::Monday => Weekday::Mon,
+            WeekStart::Sunday => Weekday::Sun,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // strftime style formats, right clicking the clock cycles through them
+    pub formats: Vec<String>,
+    // Locale used for day and month names (e.g. "de_DE"), taken from LC_TIME or LANG if unset
+    pub locale: Option<String>,
+    // IANA names (e.g. "America/New_York") shown in the tooltip and the calendar
+    pub timezones: Vec<String>,
+    pub timezone_format: String,
+    pub week_start: WeekStart,
+    pub week_numbers: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            formats: vec![
+                "%A, %B %e, %Y  %H:%M:%S".to_string(),
+                "%H:%M".to_string(),
+                "%Y-%m-%d %H:%M:%S".to_string(),
+            ],
+            locale: None,
+            timezones: Vec::new(),
+            timezone_format: "%a %H:%M".to_string(),
+            week_start: WeekStart::Monday,
+            week_numbers: true,
+        }
+    }
+}
+
+impl Config {
+    // Replaces formats chrono can't read with the defaults, formatting with them would panic
+    pub fn validate(&mut self) {
+        let defaults = Self::default();
+        for format in &mut self.formats {
+            if !valid_format(format) {
+                eprintln!("Invalid clock format {:?}, using the default", format);
+                *format = defaults.formats[0].clone();
+            }
+        }
+        if !valid_format(&self.timezone_format) {
+            eprintln!(
+                "Invalid clock timezone_format {:?}, using the default",
+                self.timezone_format
+            );
+            self.timezone_format = defaults.timezone_format;
+        }
+    }
+}
+
+// Whether a strftime style format only has specifiers chrono knows
+pub fn valid_format(format: &str) -> bool {
+    !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+}
+
+// Turns values like "de_DE.UTF-8" or "en_US@euro" into a locale chrono knows about
+pub fn parse_locale(value: &str) -> Option<Locale> {
+    let name = value.split(['.', '@']).next()?;
+    match name {
+        "" => None,
+        "C" | "POSIX" => Some(Locale::POSIX),
+        _ => Locale::try_from(name).ok(),
+    }
+}
+
+pub fn locale(config: &Config) -> Locale {
+    config
+        .locale
+        .iter()
+        .cloned()
+        .chain(
+            ["LC_ALL", "LC_TIME", "LANG"]
+                .into_iter()
+                .filter_map(|var| std::env::var(var).ok()),
+        )
+        .find_map(|value| parse_locale(&value))
+        .unwrap_or(Locale::POSIX)
+}
+
+// The weeks overlapping the month of `month`, each starting on `week_start`
+pub fn month_weeks(month: NaiveDate, week_start: Weekday) -> Vec<[NaiveDate; 7]> {
+    let first = month.with_day(1).unwrap();
+    let offset =
+        (first.weekday().num_days_from_monday() + 7 - week_start.num_days_from_monday()) % 7;
+    let mut start = first - Days::new(offset as u64);
+
+    let mut weeks = Vec::new();
+    while weeks.is_empty() || start.month() == first.month() {
+        weeks.push(std::array::from_fn(|day| start + Days::new(day as u64)));
+        start = start + Days::new(7);
+    }
+    weeks
+}
+
+// ISO week number of a row from month_weeks, which is the week number of its Monday
+pub fn week_number(week: &[NaiveDate; 7]) -> u32 {
+    week.iter()
+        .find(|day| day.weekday() == Weekday::Mon)
+        .unwrap_or(&week[0])
+        .iso_week()
+        .week()
+}
+
+// Whether the format changes every second rather than every minute
+pub fn shows_seconds(format: &str) -> bool {
+    StrftimeItems::new(format).any(|item| {
+        matches!(
+            item,
+            Item::Numeric(
+                Numeric::Second | Numeric::Nanosecond | Numeric::Timestamp,
+                _
+            ) | Item::Fixed(
+                Fixed::Nanosecond
+                    | Fixed::Nanosecond3
+                    | Fixed::Nanosecond6
+                    | Fixed::Nanosecond9
+                    | Fixed::RFC2822
+                    | Fixed::RFC3339
+            )
+        )
+    })
+}
+
+#[derive(Debug)]
+pub struct Clock {
+    pub format_index: usize,
+    // First day of the month shown in the calendar popup
+    pub calendar_month: NaiveDate,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Tick(SystemTime),
+    CycleFormat,
+    // Intercepted by the bar, which owns the popup surface
+    TogglePopup,
+    PreviousMonth,
+    NextMonth,
+    CurrentMonth,
+}
+
+fn first_of_current_month() -> NaiveDate {
+    Local::now().date_naive().with_day(1).unwrap()
+}
+
+// Formats a date at midnight, for when only its names are of interest
+fn format_date(date: NaiveDate, format: &str, locale: Locale) -> String {
+    let datetime: DateTime<Utc> = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap());
+    datetime.format_localized(format, locale).to_string()
+}
+
+impl Clock {
+    fn format<'a>(&self, config: &'a Config) -> &'a str {
+        config
+            .formats
+            .get(self.format_index)
+            .map(String::as_str)
+            .unwrap_or("%H:%M:%S")
+    }
+
+    fn timezones(config: &Config) -> Vec<(String, Tz)> {
+        config
+            .timezones
+            .iter()
+            .filter_map(|name| match name.parse::<Tz>() {
+                Ok(tz) => Some((
+                    name.rsplit('/').next().unwrap_or(name).replace('_', " "),
+                    tz,
+                )),
+                Err(error) => {
+                    dbg!(error);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn timezone_lines(config: &Config, locale: Locale) -> Vec<String> {
+        let now = Utc::now();
+        Self::timezones(config)
+            .into_iter()
+            .map(|(name, tz)| {
+                format!(
+                    "{}  {}",
+                    name,
+                    now.with_timezone(&tz)
+                        .format_localized(&config.timezone_format, locale)
+                )
+            })
+            .collect()
+    }
+
+    pub fn popup_view(self: &Self) -> Element<'_, Message> {
+        let config = config::get();
+        let config = &config.clock;
+        let locale = locale(config);
+        let today = Local::now().date_naive();
+        let weeks = month_weeks(self.calendar_month, config.week_start.weekday());
+
+        let cell = |content: String| -> iced::widget::Text<'static> {
+            text(content).width(Length::Fixed(32.0)).center()
+        };
+
+        let mut header = vec![];
+        if config.week_numbers {
+            header.push(cell("".to_string()).into());
+        }
+        header.extend(
+            weeks[0]
+                .iter()
+                .map(|day| cell(format_date(*day, "%a", locale)).size(12).into()),
+        );
+
+        let mut rows: Vec<Element<'_, Message>> = vec![row(header).into()];
+        for week in &weeks {
+            let mut cells: Vec<Element<'_, Message>> = vec![];
+            if config.week_numbers {
+                cells.push(
+                    cell(week_number(week).to_string())
+                        .size(12)
+                        .style(|theme: &Theme| text::Style {
+                            color: Some(theme.extended_palette().secondary.base.color),
+                        })
+                        .into(),
+                );
+            }
+            for day in week {
+                let in_month = day.month() == self.calendar_month.month();
+                let is_today = *day == today;
+                cells.push(
+                    container(cell(day.day().to_string()).style(move |theme: &Theme| {
+                        text::Style {
+                            color: if in_month || is_today {
+                                None
+                            } else {
+                                Some(theme.extended_palette().background.strong.color)
+                            },
+                        }
+                    }))
+                    .style(move |theme: &Theme| container::Style {
+                        background: if is_today {
+                            Some(theme.extended_palette().primary.base.color.into())
+                        } else {
+                            None
+                        },
+                        border: iced::Border {
+                            radius: iced::border::radius(16),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .into(),
+                );
+            }
+            rows.push(row(cells).into());
+        }
+
+        let mut content = column![
+            row![
+                button(text("<")).on_press(Message::PreviousMonth),
+                button(text(format_date(self.calendar_month, "%B %Y", locale)).center())
+                    .width(Length::Fill)
+                    .on_press(Message::CurrentMonth),
+                button(text(">")).on_press(Message::NextMonth),
+            ]
+            .spacing(4),
+            column(rows).spacing(2),
+        ]
+        .spacing(8);
+
+        for line in Self::timezone_lines(config, locale) {
+            content = content.push(text(line));
+        }
+
+        container(content).padding(10).into()
+    }
+}
+
+impl Module for Clock {
+    type Message = Message;
+
+    fn new() -> (Self, Task<Self::Message>) {
+        (
+            Self {
+                format_index: 0,
+                calendar_month: first_of_current_month(),
+            },
+            Task::none(),
+        )
+    }
+
+    fn view(self: &Self) -> Element<'_, Self::Message> {
+        let config = config::get();
+        let config = &config.clock;
+        let locale = locale(config);
+
+        let clock = mouse_area(text(
+            Local::now()
+                .format_localized(self.format(config), locale)
+                .to_string(),
+        ))
+        .on_press(Message::TogglePopup)
+        .on_right_press(Message::CycleFormat);
+
+        let timezones = Self::timezone_lines(config, locale);
+        if timezones.is_empty() {
+            return clock.into();
+        }
+        // The bar is too short for a tooltip below it, so show the other timezones on one line
+        tooltip(
+            clock,
+            container(text(timezones.join("   ")))
+                .padding([0, 8])
+                .style(container::rounded_box),
+            tooltip::Position::Left,
+        )
+        .into()
+    }
+
+    fn update(self: &mut Self, message: Self::Message) -> Task<Self::Message> {
+        use Message::*;
+        match message {
+            // Nothing to store, receiving it is enough for the clock to be redrawn
+            Tick(_) => Task::none(),
+            CycleFormat => {
+                let count = config::get().clock.formats.len().max(1);
+                self.format_index = (self.format_index + 1) % count;
+                Task::none()
+            }
+            TogglePopup => {
+                self.calendar_month = first_of_current_month();
+                Task::none()
+            }
+            PreviousMonth => {
+                self.calendar_month = self.calendar_month - Months::new(1);
+                Task::none()
+            }
+            NextMonth => {
+                self.calendar_month = self.calendar_month + Months::new(1);
+                Task::none()
+            }
+            CurrentMonth => {
+                self.calendar_month = first_of_current_month();
+                Task::none()
+            }
+        }
+    }
+
+    fn subscription(self: &Self) -> iced::Subscription<Self::Message> {
+        let config = config::get();
+        let config = &config.clock;
+
+        let period = if shows_seconds(self.format(config))
+            || (!config.timezones.is_empty() && shows_seconds(&config.timezone_format))
+        {
+            Duration::from_secs(1)
+        } else {
+            Duration::from_secs(60)
+        };
+        timer::aligned(period).map(Message::Tick)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn month_weeks_cover_the_month() {
+        // February 2021 starts on a Monday and fills exactly four weeks
+        let weeks = month_weeks(date(2021, 2, 14), Weekday::Mon);
+        assert_eq!(weeks.len(), 4);
+        assert_eq!(weeks[0][0], date(2021, 2, 1));
+        assert_eq!(weeks[3][6], date(2021, 2, 28));
+
+        // Starting on Sunday pulls in the last day of January
+        let weeks = month_weeks(date(2021, 2, 1), Weekday::Sun);
+        assert_eq!(weeks.len(), 5);
+        assert_eq!(weeks[0][0], date(2021, 1, 31));
+        assert_eq!(weeks[4][6], date(2021, 3, 6));
+
+        // May 2021 starts on a Saturday and ends on a Monday
+        let weeks = month_weeks(date(2021, 5, 1), Weekday::Mon);
+        assert_eq!(weeks.len(), 6);
+        assert_eq!(weeks[0][0], date(2021, 4, 26));
+        assert_eq!(weeks[5][0], date(2021, 5, 31));
+    }
+
+    #[test]
+    fn weeks_start_on_the_configured_day() {
+        for week_start in [Weekday::Mon, Weekday::Sun] {
+            for week in month_weeks(date(2024, 9, 1), week_start) {
+                assert_eq!(week[0].weekday(), week_start);
+            }
+        }
+    }
+
+    #[test]
+    fn numbers_weeks_across_year_boundaries() {
+        // 2020 has 53 ISO weeks, so the first row of January 2021 is still week 53
+        let weeks = month_weeks(date(2021, 1, 1), Weekday::Mon);
+        assert_eq!(weeks[0][0], date(2020, 12, 28));
+        assert_eq!(week_number(&weeks[0]), 53);
+        assert_eq!(week_number(&weeks[1]), 1);
+
+        // The last row of December 2024 is week 1 of 2025
+        let weeks = month_weeks(date(2024, 12, 1), Weekday::Mon);
+        assert_eq!(week_number(&weeks[0]), 48);
+        assert_eq!(week_number(weeks.last().unwrap()), 1);
+
+        // Rows starting on Sunday are numbered by their Monday
+        let weeks = month_weeks(date(2024, 12, 1), Weekday::Sun);
+        assert_eq!(weeks[0][0], date(2024, 12, 1));
+        assert_eq!(week_number(&weeks[0]), 49);
+        assert_eq!(weeks.last().unwrap()[0], date(2024, 12, 29));
+        assert_eq!(week_number(weeks.last().unwrap()), 1);
+    }
+
+    #[test]
+    fn detects_seconds() {
+        assert!(shows_seconds("%H:%M:%S"));
+        assert!(shows_seconds("%T"));
+        assert!(shows_seconds("%s"));
+        assert!(shows_seconds("%H:%M:%S%.3f"));
+        assert!(shows_seconds("%+"));
+        assert!(!shows_seconds("%H:%M"));
+        assert!(!shows_seconds("%A, %B %e, %Y"));
+        assert!(!shows_seconds("%%S"));
+    }
+
+    #[test]
+    fn parses_locales() {
+        assert_eq!(parse_locale("de_DE.UTF-8"), Some(Locale::de_DE));
+        assert_eq!(parse_locale("en_US@euro"), Some(Locale::en_US));
+        assert_eq!(parse_locale("fr_FR"), Some(Locale::fr_FR));
+        assert_eq!(parse_locale("C"), Some(Locale::POSIX));
+        assert_eq!(parse_locale("C.UTF-8"), Some(Locale::POSIX));
+        assert_eq!(parse_locale("POSIX"), Some(Locale::POSIX));
+        assert_eq!(parse_locale(""), None);
+        assert_eq!(parse_locale("xx_YY.UTF-8"), None);
+    }
+
+    #[test]
+    fn replaces_invalid_formats() {
+        assert!(valid_format("%H:%M"));
+        assert!(!valid_format("%H:%M %Q"));
+        assert!(!valid_format("%"));
+
+        let mut config = Config {
+            formats: vec!["%H:%M".to_string(), "%H:%M %Q".to_string()],
+            timezone_format: "%a %".to_string(),
+            ..Config::default()
+        };
+        config.validate();
+        let defaults = Config::default();
+        assert_eq!(config.formats, ["%H:%M", defaults.formats[0].as_str()]);
+        assert_eq!(config.timezone_format, defaults.timezone_format);
+    }
+}
@@ -0,0 +1,523 @@
+use std::process::Stdio;
+use std::time::Duration;
+
+use cosmic::iced::futures::SinkExt;
+use cosmic::iced::mouse::ScrollDelta;
+use cosmic::iced::{self, Element, Subscription, Task};
+use cosmic::iced_widget::row;
+use iced::widget::{container, mouse_area, text, tooltip};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+use super::module::Module;
+use super::{Level, level_style};
+use crate::config;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReturnType {
+    // Text on the first line, tooltip on the second and class on the third
+    #[default]
+    Text,
+    // An object with text, tooltip, class and percentage keys, like waybar's custom modules
+    Json,
+}
+
+// One [[custom]] entry in the config
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(default)]
+pub struct Script {
+    // Run with sh -c
+    pub exec: String,
+    // Seconds between runs. Without it the command is kept running and every line it
+    // prints replaces the previous output.
+    pub interval: Option<u64>,
+    // Seconds to wait before starting a long-running command again after it exits
+    pub restart_interval: Option<u64>,
+    pub return_type: ReturnType,
+    // Replaces {text} and {percentage}, defaults to just the text
+    pub format: Option<String>,
+    pub on_click: Option<String>,
+    pub on_right_click: Option<String>,
+    pub on_middle_click: Option<String>,
+    pub on_scroll_up: Option<String>,
+    pub on_scroll_down: Option<String>,
+}
+
+// Waybar allows a single class or a list of them
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+enum Classes {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+struct JsonOutput {
+    text: String,
+    tooltip: Option<String>,
+    class: Option<Classes>,
+    percentage: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Output {
+    pub text: String,
+    pub tooltip: Option<String>,
+    pub classes: Vec<String>,
+    pub percentage: Option<f32>,
+}
+
+impl Output {
+    pub fn parse(stdout: &str, return_type: ReturnType) -> Result<Self, String> {
+        match return_type {
+            ReturnType::Text => {
+                let mut lines = stdout.lines();
+                let text = lines.next().unwrap_or_default().to_string();
+                let tooltip = lines
+                    .next()
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string);
+                let classes = lines
+                    .next()
+                    .map(|line| line.split_whitespace().map(str::to_string).collect())
+                    .unwrap_or_default();
+                Ok(Self {
+                    text,
+                    tooltip,
+                    classes,
+                    percentage: None,
+                })
+            }
+            ReturnType::Json => {
+                let output: JsonOutput =
+                    serde_json::from_str(stdout.trim()).map_err(|error| error.to_string())?;
+                Ok(Self {
+                    text: output.text,
+                    tooltip: output.tooltip.filter(|tooltip| !tooltip.is_empty()),
+                    classes: match output.class {
+                        Some(Classes::One(class)) => vec![class],
+                        Some(Classes::Many(classes)) => classes,
+                        None => Vec::new(),
+                    },
+                    percentage: output.percentage,
+                })
+            }
+        }
+    }
+
+    pub fn level(&self) -> Level {
+        if self.classes.iter().any(|class| class == "critical") {
+            Level::Critical
+        } else if self.classes.iter().any(|class| class == "warning") {
+            Level::Warning
+        } else {
+            Level::Normal
+        }
+    }
+
+    pub fn format(&self, format: Option<&str>) -> String {
+        match format {
+            Some(format) => format.replace("{text}", &self.text).replace(
+                "{percentage}",
+                &self
+                    .percentage
+                    .map(|percentage| format!("{:.0}", percentage))
+                    .unwrap_or_default(),
+            ),
+            None => self.text.clone(),
+        }
+    }
+}
+
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        // Its own process group, so pipelines and background jobs can be killed along with it
+        .process_group(0);
+    shell
+}
+
+// Kills a command's whole process group if dropped while it runs, which is what happens to
+// scripts still running when a config reload drops their subscription
+struct Running(Option<u32>);
+
+impl Running {
+    fn finished(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        if let Some(pid) = self.0 {
+            unsafe {
+                libc::killpg(pid as libc::pid_t, libc::SIGTERM);
+            }
+        }
+    }
+}
+
+fn run(index: usize, script: Script) -> impl iced::futures::Stream<Item = Message> {
+    iced::stream::channel(10, async move |mut output| {
+        loop {
+            match script.interval {
+                Some(interval) => {
+                    let result = match shell_command(&script.exec).stdout(Stdio::piped()).spawn() {
+                        Ok(child) => {
+                            let running = Running(child.id());
+                            let result = child.wait_with_output().await;
+                            running.finished();
+                            result
+                        }
+                        Err(error) => Err(error),
+                    };
+                    match result {
+                        Ok(result) => {
+                            let stdout = String::from_utf8_lossy(&result.stdout);
+                            let _ = output
+                                .send(Message::Updated(
+                                    index,
+                                    Output::parse(&stdout, script.return_type),
+                                ))
+                                .await;
+                        }
+                        Err(error) => {
+                            dbg!(error);
+                        }
+                    }
+                    tokio::time::sleep(Duration::from_secs(interval.max(1))).await;
+                }
+                None => {
+                    let mut child = match shell_command(&script.exec).stdout(Stdio::piped()).spawn()
+                    {
+                        Ok(child) => child,
+                        Err(error) => {
+                            dbg!(error);
+                            return;
+                        }
+                    };
+                    let running = Running(child.id());
+
+                    if let Some(stdout) = child.stdout.take() {
+                        let mut lines = BufReader::new(stdout).lines();
+                        while let Ok(Some(line)) = lines.next_line().await {
+                            let _ = output
+                                .send(Message::Updated(
+                                    index,
+                                    Output::parse(&line, script.return_type),
+                                ))
+                                .await;
+                        }
+                    }
+                    let _ = child.wait().await;
+                    running.finished();
+
+                    match script.restart_interval {
+                        Some(restart_interval) => {
+                            tokio::time::sleep(Duration::from_secs(restart_interval)).await
+                        }
+                        None => return,
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Trigger {
+    Click,
+    RightClick,
+    MiddleClick,
+    ScrollUp,
+    ScrollDown,
+}
+
+#[derive(Debug)]
+pub struct Custom {
+    // Indexed like the [[custom]] entries in the config
+    pub outputs: Vec<Option<Output>>,
+    // The entries the outputs came from, to tell which ones a reload changed
+    pub scripts: Vec<Script>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Updated(usize, Result<Output, String>),
+    Action(usize, Trigger),
+    ActionFinished(Result<(), String>),
+    // Sent by the shell after the config file is read again
+    ConfigReloaded,
+}
+
+impl Module for Custom {
+    type Message = Message;
+
+    fn new() -> (Self, Task<Self::Message>) {
+        (
+            Self {
+                outputs: Vec::new(),
+                scripts: config::get().custom.clone(),
+            },
+            Task::none(),
+        )
+    }
+
+    fn view(self: &Self) -> Element<'_, Self::Message> {
+        let config = config::get();
+
+        row(config
+            .custom
+            .iter()
+            .enumerate()
+            .filter_map(|(index, script)| {
+                let output = self.outputs.get(index)?.as_ref()?;
+                if output.text.is_empty() {
+                    return None;
+                }
+
+                let label = mouse_area(
+                    text(output.format(script.format.as_deref()))
+                        .style(level_style(output.level())),
+                )
+                .on_press(Message::Action(index, Trigger::Click))
+                .on_right_press(Message::Action(index, Trigger::RightClick))
+                .on_middle_press(Message::Action(index, Trigger::MiddleClick))
+                .on_scroll(move |delta| {
+                    let y = match delta {
+                        ScrollDelta::Lines { y, .. } => y,
+                        ScrollDelta::Pixels { y, .. } => y,
+                    };
+                    Message::Action(
+                        index,
+                        if y > 0.0 {
+                            Trigger::ScrollUp
+                        } else {
+                            Trigger::ScrollDown
+                        },
+                    )
+                });
+
+                Some(match &output.tooltip {
+                    // The bar is too short for a tooltip below it, so keep it on one line
+                    Some(tooltip_text) => tooltip(
+                        label,
+                        container(text(tooltip_text.replace('\n', "   ")))
+                            .padding([0, 8])
+                            .style(container::rounded_box),
+                        tooltip::Position::Left,
+                    )
+                    .into(),
+                    None => label.into(),
+                })
+            }))
+        .spacing(10)
+        .into()
+    }
+
+    fn update(self: &mut Self, message: Self::Message) -> Task<Self::Message> {
+        use Message::*;
+        match message {
+            Updated(index, Ok(output)) => {
+                if self.outputs.len() <= index {
+                    self.outputs.resize(index + 1, None);
+                }
+                self.outputs[index] = Some(output);
+                Task::none()
+            }
+            Updated(index, Err(error)) => {
+                dbg!(index, error);
+                Task::none()
+            }
+            Action(index, trigger) => {
+                let config = config::get();
+                let Some(script) = config.custom.get(index) else {
+                    return Task::none();
+                };
+                let command = match trigger {
+                    Trigger::Click => &script.on_click,
+                    Trigger::RightClick => &script.on_right_click,
+                    Trigger::MiddleClick => &script.on_middle_click,
+                    Trigger::ScrollUp => &script.on_scroll_up,
+                    Trigger::ScrollDown => &script.on_scroll_down,
+                };
+                let Some(command) = command.clone() else {
+                    return Task::none();
+                };
+
+                // Actions are meant to outlive the bar's subscriptions, so they aren't killed
+                Task::perform(
+                    async move {
+                        shell_command(&command)
+                            .status()
+                            .await
+                            .map(|_| ())
+                            .map_err(|error| error.to_string())
+                    },
+                    ActionFinished,
+                )
+            }
+            ConfigReloaded => {
+                // Outputs stay with their index, so drop the ones whose entry was changed, moved
+                // or removed instead of showing them under another script until it runs again
+                let scripts = config::get().custom.clone();
+                for (index, output) in self.outputs.iter_mut().enumerate() {
+                    if self.scripts.get(index) != scripts.get(index) {
+                        *output = None;
+                    }
+                }
+                self.scripts = scripts;
+                Task::none()
+            }
+            ActionFinished(Ok(())) => Task::none(),
+            ActionFinished(Err(error)) => {
+                dbg!(error);
+                Task::none()
+            }
+        }
+    }
+
+    fn subscription(self: &Self) -> iced::Subscription<Self::Message> {
+        // Keyed on the whole definition, so a changed entry restarts while others keep running
+        Subscription::batch(
+            config::get()
+                .custom
+                .iter()
+                .enumerate()
+                .filter(|(_, script)| !script.exec.is_empty())
+                .map(|(index, script)| {
+                    Subscription::run_with_id((index, script.clone()), run(index, script.clone()))
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classes(classes: &[&str]) -> Vec<String> {
+        classes.iter().map(|class| class.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_text_lines() {
+        let output = Output::parse("42°C\nCPU package\nwarning hot\n", ReturnType::Text).unwrap();
+        assert_eq!(
+            output,
+            Output {
+                text: "42°C".to_string(),
+                tooltip: Some("CPU package".to_string()),
+                classes: classes(&["warning", "hot"]),
+                percentage: None,
+            }
+        );
+        assert_eq!(output.level(), Level::Warning);
+    }
+
+    #[test]
+    fn text_lines_are_optional() {
+        let output = Output::parse("just text", ReturnType::Text).unwrap();
+        assert_eq!(output.text, "just text");
+        assert_eq!(output.tooltip, None);
+        assert!(output.classes.is_empty());
+
+        // An empty second line is no tooltip, but the class after it still counts
+        let output = Output::parse("text\n\ncritical", ReturnType::Text).unwrap();
+        assert_eq!(output.tooltip, None);
+        assert_eq!(output.classes, classes(&["critical"]));
+
+        assert_eq!(
+            Output::parse("", ReturnType::Text).unwrap(),
+            Output::default()
+        );
+    }
+
+    #[test]
+    fn keeps_json_in_text_mode() {
+        let output = Output::parse(r#"{"text": "hi"}"#, ReturnType::Text).unwrap();
+        assert_eq!(output.text, r#"{"text": "hi"}"#);
+    }
+
+    #[test]
+    fn parses_json() {
+        let output = Output::parse(
+            r#"{"text": "50%", "tooltip": "Volume", "class": "muted", "percentage": 50}"#,
+            ReturnType::Json,
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            Output {
+                text: "50%".to_string(),
+                tooltip: Some("Volume".to_string()),
+                classes: classes(&["muted"]),
+                percentage: Some(50.0),
+            }
+        );
+    }
+
+    #[test]
+    fn takes_json_class_as_string_or_list() {
+        let parse = |json: &str| Output::parse(json, ReturnType::Json).unwrap().classes;
+        assert_eq!(parse(r#"{"class": "critical"}"#), classes(&["critical"]));
+        assert_eq!(
+            parse(r#"{"class": ["warning", "charging"]}"#),
+            classes(&["warning", "charging"])
+        );
+        assert_eq!(parse(r#"{"class": []}"#), classes(&[]));
+        assert_eq!(parse(r#"{"text": "no class"}"#), classes(&[]));
+    }
+
+    #[test]
+    fn json_keys_are_optional() {
+        // Trailing newlines from echo are fine, unknown keys are ignored
+        let output =
+            Output::parse("{\"alt\": \"x\", \"tooltip\": \"\"}\n", ReturnType::Json).unwrap();
+        assert_eq!(output, Output::default());
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        assert!(Output::parse("not json", ReturnType::Json).is_err());
+        assert!(Output::parse(r#"{"class": 3}"#, ReturnType::Json).is_err());
+        assert!(Output::parse("", ReturnType::Json).is_err());
+    }
+
+    #[test]
+    fn picks_level_from_classes() {
+        let level = |names: &[&str]| {
+            Output {
+                classes: classes(names),
+                ..Output::default()
+            }
+            .level()
+        };
+        assert_eq!(level(&[]), Level::Normal);
+        assert_eq!(level(&["warning"]), Level::Warning);
+        assert_eq!(level(&["warning", "critical"]), Level::Critical);
+        assert_eq!(level(&["Critical"]), Level::Normal);
+    }
+
+    #[test]
+    fn formats_text_and_percentage() {
+        let output = Output {
+            text: "Volume".to_string(),
+            percentage: Some(49.6),
+            ..Output::default()
+        };
+        assert_eq!(output.format(None), "Volume");
+        assert_eq!(output.format(Some("{text}: {percentage}%")), "Volume: 50%");
+        let output = Output {
+            percentage: None,
+            ..output
+        };
+        assert_eq!(output.format(Some("{percentage}%")), "%");
+    }
+}
@@ -0,0 +1,485 @@
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use cosmic::iced::{self, Element, Length, Task};
+use cosmic::iced_widget::{column, row, scrollable};
+use iced::widget::{container, mouse_area, text};
+use regex::Regex;
+use serde::Deserialize;
+
+use super::module::Module;
+use super::timer;
+use super::{Level, level_style};
+use crate::config;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Sensor shown in the bar, as "chip:label", "chip" or "label" (e.g. "k10temp:Tctl")
+    pub sensor: Option<String>,
+    // Fan shown next to the temperature, using the same syntax as the sensor
+    pub fan: Option<String>,
+    // Override the thresholds reported by the driver, in °C
+    pub warning: Option<f32>,
+    pub critical: Option<f32>,
+    // How often the sensors are read, in milliseconds
+    pub interval: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sensor: None,
+            fan: None,
+            warning: None,
+            critical: None,
+            interval: 2000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorKind {
+    Temperature,
+    Fan,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sensor {
+    // Contents of the chip's name file, stable across boots unlike the hwmonN directory
+    pub chip: String,
+    pub label: String,
+    pub kind: SensorKind,
+    // °C for temperatures, RPM for fans
+    pub value: f32,
+    pub max: Option<f32>,
+    pub critical: Option<f32>,
+}
+
+impl Sensor {
+    pub fn matches(&self, selector: &str) -> bool {
+        match selector.split_once(':') {
+            Some((chip, label)) => {
+                self.chip.eq_ignore_ascii_case(chip) && self.label.eq_ignore_ascii_case(label)
+            }
+            None => {
+                self.chip.eq_ignore_ascii_case(selector)
+                    || self.label.eq_ignore_ascii_case(selector)
+            }
+        }
+    }
+
+    pub fn level(&self, config: &Config) -> Level {
+        match self.kind {
+            SensorKind::Fan => Level::Normal,
+            SensorKind::Temperature => {
+                let critical = config.critical.or(self.critical).unwrap_or(90.0);
+                let warning = config.warning.or(self.max).unwrap_or(critical - 10.0);
+                Level::from_thresholds(self.value, warning, critical)
+            }
+        }
+    }
+
+    pub fn format_value(&self) -> String {
+        match self.kind {
+            SensorKind::Temperature => format!("{:.0}°C", self.value),
+            SensorKind::Fan => format!("{:.0} RPM", self.value),
+        }
+    }
+}
+
+static INPUT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("^(?<kind>temp|fan)(?<index>[0-9]+)_input$").unwrap());
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|contents| contents.trim().to_string())
+}
+
+fn read_number(path: &Path) -> Option<f32> {
+    read_trimmed(path)?.parse().ok()
+}
+
+// Reads every temperature and fan input of every chip under a /sys/class/hwmon style directory
+pub fn read_sensors(root: &Path) -> Vec<Sensor> {
+    let mut chips: Vec<_> = match std::fs::read_dir(root) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|e| e.path())
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+    chips.sort();
+
+    let mut sensors = Vec::new();
+    for chip_dir in chips {
+        let Some(chip) = read_trimmed(&chip_dir.join("name")) else {
+            continue;
+        };
+
+        let mut inputs: Vec<(SensorKind, u32)> = match std::fs::read_dir(&chip_dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let file_name = entry.file_name();
+                    let captures = INPUT_RE.captures(file_name.to_str()?)?;
+                    let kind = match &captures["kind"] {
+                        "temp" => SensorKind::Temperature,
+                        _ => SensorKind::Fan,
+                    };
+                    Some((kind, captures["index"].parse().ok()?))
+                })
+                .collect(),
+            Err(_) => continue,
+        };
+        inputs.sort_by_key(|(kind, index)| (*kind == SensorKind::Fan, *index));
+
+        for (kind, index) in inputs {
+            let prefix = match kind {
+                SensorKind::Temperature => format!("temp{}", index),
+                SensorKind::Fan => format!("fan{}", index),
+            };
+            // Temperatures are reported in millidegrees
+            let scale = match kind {
+                SensorKind::Temperature => 1000.0,
+                SensorKind::Fan => 1.0,
+            };
+            let Some(value) = read_number(&chip_dir.join(format!("{}_input", prefix))) else {
+                continue;
+            };
+
+            sensors.push(Sensor {
+                chip: chip.clone(),
+                label: read_trimmed(&chip_dir.join(format!("{}_label", prefix)))
+                    .unwrap_or(prefix.clone()),
+                kind,
+                value: value / scale,
+                max: read_number(&chip_dir.join(format!("{}_max", prefix))).map(|v| v / scale),
+                critical: read_number(&chip_dir.join(format!("{}_crit", prefix)))
+                    .map(|v| v / scale),
+            });
+        }
+    }
+    sensors
+}
+
+#[derive(Debug)]
+pub struct Hwmon {
+    pub sensors: Vec<Sensor>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Sensors(Vec<Sensor>),
+    // Intercepted by the bar, which owns the popup surface
+    TogglePopup,
+}
+
+impl Hwmon {
+    // The sensor shown in the bar, the first temperature if none is configured
+    fn selected(&self, selector: &Option<String>, kind: SensorKind) -> Option<&Sensor> {
+        let mut candidates = self.sensors.iter().filter(|sensor| sensor.kind == kind);
+        match selector {
+            Some(selector) => candidates.find(|sensor| sensor.matches(selector)),
+            None if kind == SensorKind::Temperature => candidates.next(),
+            None => None,
+        }
+    }
+
+    pub fn popup_view(self: &Self) -> Element<'_, Message> {
+        let config = config::get();
+        let config = &config.hwmon;
+
+        let mut chips: Vec<Element<'_, Message>> = vec![];
+        let mut current_chip: Option<&str> = None;
+        for sensor in &self.sensors {
+            if current_chip != Some(sensor.chip.as_str()) {
+                current_chip = Some(sensor.chip.as_str());
+                chips.push(text(&sensor.chip).size(16).into());
+            }
+            chips.push(
+                row![
+                    text(&sensor.label).width(Length::Fill),
+                    text(sensor.format_value()).style(level_style(sensor.level(config))),
+                ]
+                .padding([0, 10])
+                .into(),
+            );
+        }
+
+        container(scrollable(column(chips).spacing(4)))
+            .padding(10)
+            .into()
+    }
+}
+
+impl Module for Hwmon {
+    type Message = Message;
+
+    fn new() -> (Self, Task<Self::Message>) {
+        (
+            Self {
+                sensors: read_sensors(Path::new("/sys/class/hwmon")),
+            },
+            Task::none(),
+        )
+    }
+
+    fn view(self: &Self) -> Element<'_, Self::Message> {
+        let config = config::get();
+        let config = &config.hwmon;
+
+        let mut items: Vec<Element<'_, Self::Message>> = vec![];
+        if let Some(sensor) = self.selected(&config.sensor, SensorKind::Temperature) {
+            items.push(
+                text(sensor.format_value())
+                    .style(level_style(sensor.level(config)))
+                    .into(),
+            );
+        }
+        if let Some(fan) = self.selected(&config.fan, SensorKind::Fan) {
+            items.push(text(fan.format_value()).into());
+        }
+
+        mouse_area(row(items).spacing(6))
+            .on_press(Message::TogglePopup)
+            .into()
+    }
+
+    fn update(self: &mut Self, message: Self::Message) -> Task<Self::Message> {
+        use Message::*;
+        match message {
+            Sensors(sensors) => {
+                self.sensors = sensors;
+                Task::none()
+            }
+            TogglePopup => Task::none(),
+        }
+    }
+
+    fn subscription(self: &Self) -> iced::Subscription<Self::Message> {
+        timer::poll_changes(
+            "hwmon",
+            Duration::from_millis(config::get().hwmon.interval.max(100)),
+            || read_sensors(Path::new("/sys/class/hwmon")),
+        )
+        .map(Message::Sensors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // A /sys/class/hwmon lookalike in a temporary directory, removed when dropped
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!("hwmon-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(&root).unwrap();
+            Self(root)
+        }
+
+        fn chip(&self, dir: &str, files: &[(&str, &str)]) -> &Self {
+            let dir = self.0.join(dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            for (name, contents) in files {
+                std::fs::write(dir.join(name), format!("{}\n", contents)).unwrap();
+            }
+            self
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn laptop(name: &str) -> Fixture {
+        let fixture = Fixture::new(name);
+        fixture
+            .chip(
+                "hwmon0",
+                &[
+                    ("name", "k10temp"),
+                    ("temp1_input", "54250"),
+                    ("temp1_label", "Tctl"),
+                    ("temp3_input", "48000"),
+                    ("temp3_label", "Tccd1"),
+                ],
+            )
+            .chip(
+                "hwmon1",
+                &[
+                    ("name", "thinkpad"),
+                    ("fan1_input", "2650"),
+                    ("temp1_input", "61000"),
+                    ("temp1_max", "80000"),
+                    ("temp1_crit", "95000"),
+                    ("temp10_input", "40000"),
+                ],
+            )
+            // Not a sensor chip, no name file
+            .chip("hwmon2", &[("temp1_input", "1000")])
+            .chip(
+                "hwmon3",
+                &[
+                    ("name", "nvme"),
+                    ("temp1_input", "38850"),
+                    ("temp1_label", "Composite"),
+                    ("temp2_label", "Sensor 1"),
+                ],
+            );
+        fixture
+    }
+
+    fn sensor(chip: &str, label: &str, value: f32) -> Sensor {
+        Sensor {
+            chip: chip.to_string(),
+            label: label.to_string(),
+            kind: SensorKind::Temperature,
+            value,
+            max: None,
+            critical: None,
+        }
+    }
+
+    #[test]
+    fn reads_sensors() {
+        let fixture = laptop("reads");
+        let sensors = read_sensors(&fixture.0);
+        let names: Vec<_> = sensors
+            .iter()
+            .map(|sensor| format!("{}:{}", sensor.chip, sensor.label))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "k10temp:Tctl",
+                "k10temp:Tccd1",
+                "thinkpad:temp1",
+                "thinkpad:temp10",
+                "thinkpad:fan1",
+                "nvme:Composite",
+            ]
+        );
+    }
+
+    #[test]
+    fn scales_values() {
+        let fixture = laptop("scales");
+        let sensors = read_sensors(&fixture.0);
+
+        let tctl = &sensors[0];
+        assert_eq!(tctl.kind, SensorKind::Temperature);
+        assert_eq!(tctl.value, 54.25);
+        assert_eq!(tctl.format_value(), "54°C");
+
+        let thinkpad = &sensors[2];
+        assert_eq!(thinkpad.max, Some(80.0));
+        assert_eq!(thinkpad.critical, Some(95.0));
+
+        let fan = &sensors[4];
+        assert_eq!(fan.kind, SensorKind::Fan);
+        assert_eq!(fan.value, 2650.0);
+        assert_eq!(fan.max, None);
+        assert_eq!(fan.format_value(), "2650 RPM");
+    }
+
+    #[test]
+    fn missing_root_has_no_sensors() {
+        assert!(read_sensors(Path::new("/nonexistent/hwmon")).is_empty());
+    }
+
+    #[test]
+    fn selects_sensors() {
+        let fixture = laptop("selects");
+        let hwmon = Hwmon {
+            sensors: read_sensors(&fixture.0),
+        };
+        let selected = |selector: Option<&str>, kind| {
+            hwmon
+                .selected(&selector.map(str::to_string), kind)
+                .map(|sensor| format!("{}:{}", sensor.chip, sensor.label))
+        };
+
+        // The first temperature by default, but no fan unless asked for
+        assert_eq!(
+            selected(None, SensorKind::Temperature).as_deref(),
+            Some("k10temp:Tctl")
+        );
+        assert_eq!(selected(None, SensorKind::Fan), None);
+
+        assert_eq!(
+            selected(Some("k10temp:tccd1"), SensorKind::Temperature).as_deref(),
+            Some("k10temp:Tccd1")
+        );
+        assert_eq!(
+            selected(Some("nvme"), SensorKind::Temperature).as_deref(),
+            Some("nvme:Composite")
+        );
+        assert_eq!(
+            selected(Some("Composite"), SensorKind::Temperature).as_deref(),
+            Some("nvme:Composite")
+        );
+        assert_eq!(
+            selected(Some("thinkpad"), SensorKind::Fan).as_deref(),
+            Some("thinkpad:fan1")
+        );
+        // Both halves have to match
+        assert_eq!(selected(Some("nvme:Tctl"), SensorKind::Temperature), None);
+    }
+
+    #[test]
+    fn falls_back_to_default_thresholds() {
+        let config = Config::default();
+        assert_eq!(sensor("cpu", "Tctl", 79.0).level(&config), Level::Normal);
+        assert_eq!(sensor("cpu", "Tctl", 80.0).level(&config), Level::Warning);
+        assert_eq!(sensor("cpu", "Tctl", 90.0).level(&config), Level::Critical);
+    }
+
+    #[test]
+    fn uses_driver_thresholds() {
+        let config = Config::default();
+        let mut reading = sensor("cpu", "Tctl", 70.0);
+        reading.max = Some(65.0);
+        reading.critical = Some(100.0);
+        assert_eq!(reading.level(&config), Level::Warning);
+
+        // Without a max the warning is 10°C below critical
+        reading.max = None;
+        assert_eq!(reading.level(&config), Level::Normal);
+        reading.value = 90.0;
+        assert_eq!(reading.level(&config), Level::Warning);
+        reading.value = 100.0;
+        assert_eq!(reading.level(&config), Level::Critical);
+    }
+
+    #[test]
+    fn config_overrides_driver_thresholds() {
+        let config = Config {
+            warning: Some(50.0),
+            critical: Some(60.0),
+            ..Config::default()
+        };
+        let mut reading = sensor("cpu", "Tctl", 55.0);
+        reading.max = Some(80.0);
+        reading.critical = Some(95.0);
+        assert_eq!(reading.level(&config), Level::Warning);
+        reading.value = 60.0;
+        assert_eq!(reading.level(&config), Level::Critical);
+    }
+
+    #[test]
+    fn fans_are_never_alarming() {
+        let mut fan = sensor("thinkpad", "fan1", 9000.0);
+        fan.kind = SensorKind::Fan;
+        assert_eq!(fan.level(&Config::default()), Level::Normal);
+    }
+}
@@ -0,0 +1,13 @@
+use cosmic::iced::{self, Element, Task};
+
+// A self-contained piece of the bar, wrapped by one of the variants of bar::Message
+pub trait Module {
+    type Message;
+
+    fn new() -> (Self, Task<Self::Message>)
+    where
+        Self: Sized;
+    fn view(self: &Self) -> Element<'_, Self::Message>;
+    fn update(self: &mut Self, message: Self::Message) -> Task<Self::Message>;
+    fn subscription(self: &Self) -> iced::Subscription<Self::Message>;
+}
@@ -0,0 +1,189 @@
+use cosmic::cctk;
+use cosmic::cctk::sctk::shell::wlr_layer::Anchor;
+use cosmic::iced::event::Status;
+use cosmic::iced::keyboard::Key;
+use cosmic::iced::keyboard::key::Named;
+use cosmic::iced::{self, Event, Rectangle, Subscription, Task, event, keyboard, mouse, window};
+use cosmic::iced_runtime::platform_specific::wayland::layer_surface::{
+    IcedMargin, IcedOutput, SctkLayerSurfaceSettings,
+};
+use cosmic::iced_winit::commands::layer_surface::{destroy_layer_surface, get_layer_surface};
+use cosmic::iced_winit::commands::subsurface::KeyboardInteractivity;
+use iced::widget::container;
+
+// Dropdowns opened below the bar by its modules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Popup {
+    Sensors,
+    Calendar,
+}
+
+impl Popup {
+    pub fn size(self) -> (u32, u32) {
+        match self {
+            Popup::Sensors => (320, 400),
+            Popup::Calendar => (300, 340),
+        }
+    }
+
+    // Id of the container wrapping the module the popup is opened from
+    pub fn anchor(self) -> container::Id {
+        match self {
+            Popup::Sensors => container::Id::new("popup-anchor-sensors"),
+            Popup::Calendar => container::Id::new("popup-anchor-calendar"),
+        }
+    }
+}
+
+// Id of the bar's outermost container, whose bounds are the size of the output
+pub fn bar_anchor() -> container::Id {
+    container::Id::new("popup-anchor-bar")
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OpenPopup {
+    pub popup: Popup,
+    pub id: window::Id,
+    // Transparent surface covering the rest of the screen, so clicks outside the popup
+    // reach us and can close it, like they do for the launcher
+    pub backdrop: window::Id,
+}
+
+#[derive(Debug, Default)]
+pub struct Popups {
+    pub open: Option<OpenPopup>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Toggle(Popup),
+    // The bounds of the popup's anchor and of the bar, once they've been looked up
+    Open(Popup, Option<Rectangle>, Option<Rectangle>),
+    Close,
+    ClickedOutside(window::Id),
+}
+
+// Horizontal offset that centers a popup under its anchor without leaving the bar
+pub fn left_margin(anchor: Rectangle, bar: Rectangle, width: u32) -> i32 {
+    let max = (bar.width - width as f32).max(0.0);
+    (anchor.center_x() - width as f32 / 2.0).clamp(0.0, max) as i32
+}
+
+impl Popups {
+    pub fn popup(&self, id: window::Id) -> Option<Popup> {
+        self.open
+            .filter(|open| open.id == id)
+            .map(|open| open.popup)
+    }
+
+    pub fn is_backdrop(&self, id: window::Id) -> bool {
+        self.open.is_some_and(|open| open.backdrop == id)
+    }
+
+    fn close(&mut self) -> Task<Message> {
+        match self.open.take() {
+            Some(open) => Task::batch(vec![
+                destroy_layer_surface(open.id),
+                destroy_layer_surface(open.backdrop),
+            ]),
+            None => Task::none(),
+        }
+    }
+
+    pub fn update(&mut self, message: Message) -> Task<Message> {
+        use Message::*;
+        match message {
+            Toggle(popup) => {
+                let was_open = self.open.is_some_and(|open| open.popup == popup);
+                let close = self.close();
+                if was_open {
+                    return close;
+                }
+
+                Task::batch(vec![
+                    close,
+                    container::visible_bounds(popup.anchor()).then(move |anchor| {
+                        container::visible_bounds(bar_anchor())
+                            .map(move |bar| Open(popup, anchor, bar))
+                    }),
+                ])
+            }
+            Open(popup, anchor, bar) => {
+                // Another popup may have been toggled while the bounds were looked up
+                let close = self.close();
+
+                let (width, height) = popup.size();
+                let (anchor_edge, margin) = match (anchor, bar) {
+                    (Some(anchor), Some(bar)) => (
+                        Anchor::LEFT,
+                        IcedMargin {
+                            left: left_margin(anchor, bar, width),
+                            ..Default::default()
+                        },
+                    ),
+                    _ => (Anchor::RIGHT, IcedMargin::default()),
+                };
+
+                let id = window::Id::unique();
+                let backdrop = window::Id::unique();
+                self.open = Some(OpenPopup {
+                    popup,
+                    id,
+                    backdrop,
+                });
+
+                Task::batch(vec![
+                    close,
+                    // Top layer with no exclusive zone of its own, so it stays out of the
+                    // bar's way and clicking another module switches popups directly
+                    get_layer_surface(SctkLayerSurfaceSettings {
+                        id: backdrop,
+                        layer: cctk::sctk::shell::wlr_layer::Layer::Top,
+                        anchor: Anchor::all(),
+                        exclusive_zone: 0,
+                        output: IcedOutput::Active,
+                        keyboard_interactivity: KeyboardInteractivity::None,
+                        pointer_interactivity: true,
+                        ..Default::default()
+                    }),
+                    get_layer_surface(SctkLayerSurfaceSettings {
+                        id,
+                        size: Some((Some(width), Some(height))),
+                        layer: cctk::sctk::shell::wlr_layer::Layer::Overlay,
+                        anchor: Anchor::TOP | anchor_edge,
+                        margin,
+                        exclusive_zone: 0,
+                        output: IcedOutput::Active,
+                        keyboard_interactivity: KeyboardInteractivity::OnDemand,
+                        pointer_interactivity: true,
+                        ..Default::default()
+                    }),
+                ])
+            }
+            Close => self.close(),
+            ClickedOutside(id) => {
+                if self.open.is_some_and(|open| open.id != id) {
+                    return self.close();
+                }
+                Task::none()
+            }
+        }
+    }
+
+    pub fn subscription(&self) -> Subscription<Message> {
+        if self.open.is_none() {
+            return Subscription::none();
+        }
+
+        event::listen_with(|event, status, id: window::Id| match event {
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key: Key::Named(Named::Escape),
+                ..
+            }) => Some(Message::Close),
+            Event::Mouse(mouse::Event::ButtonPressed(_)) if status == Status::Ignored => {
+                Some(Message::ClickedOutside(id))
+            }
+            _ => None,
+        })
+    }
+}
@@ -0,0 +1,544 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::Duration;
+
+use cosmic::iced::{self, Element, Task};
+use cosmic::iced_widget::row;
+use iced::widget::text;
+use serde::Deserialize;
+
+use super::module::Module;
+use super::timer;
+use super::{Level, level_style};
+use crate::config;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // How often /proc is sampled, in milliseconds
+    pub interval: u64,
+    // Number of samples kept for the sparkline, 0 hides it
+    pub history: usize,
+    // Show a bar per core next to the total usage
+    pub per_core: bool,
+    pub cpu_warning: f32,
+    pub cpu_critical: f32,
+    pub memory_warning: f32,
+    pub memory_critical: f32,
+    // Compared against the one minute load average divided by the number of cores
+    pub load_warning: f32,
+    pub load_critical: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            interval: 2000,
+            history: 20,
+            per_core: false,
+            cpu_warning: 70.0,
+            cpu_critical: 90.0,
+            memory_warning: 75.0,
+            memory_critical: 90.0,
+            load_warning: 1.0,
+            load_critical: 2.0,
+        }
+    }
+}
+
+// Jiffies spent by a cpu (or all of them, for the first line of /proc/stat)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CpuTimes {
+    pub idle: u64,
+    pub total: u64,
+}
+
+impl CpuTimes {
+    // Percentage of the time between the two samples that wasn't spent idle
+    pub fn usage_since(&self, previous: &CpuTimes) -> f32 {
+        let total = self.total.saturating_sub(previous.total);
+        if total == 0 {
+            return 0.0;
+        }
+        let idle = self.idle.saturating_sub(previous.idle);
+        (total.saturating_sub(idle) as f32 / total as f32) * 100.0
+    }
+}
+
+// All values are in kB, like in /proc/meminfo
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MemInfo {
+    pub total: u64,
+    pub available: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+}
+
+impl MemInfo {
+    pub fn used_percent(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        (self.total.saturating_sub(self.available) as f32 / self.total as f32) * 100.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LoadAvg {
+    pub one: f32,
+    pub five: f32,
+    pub fifteen: f32,
+}
+
+// Returns the aggregate "cpu" line first, followed by one entry per "cpuN" line
+pub fn parse_stat(contents: &str) -> Vec<CpuTimes> {
+    contents
+        .lines()
+        .filter(|line| line.starts_with("cpu"))
+        .filter_map(|line| {
+            let fields: Vec<u64> = line
+                .split_whitespace()
+                .skip(1)
+                .map(|field| field.parse().ok())
+                .collect::<Option<_>>()?;
+            if fields.len() < 4 {
+                return None;
+            }
+            // user nice system idle iowait irq softirq steal, guest time is already counted in user
+            let total = fields.iter().take(8).sum();
+            let idle = fields[3] + fields.get(4).copied().unwrap_or(0);
+            Some(CpuTimes { idle, total })
+        })
+        .collect()
+}
+
+pub fn parse_meminfo(contents: &str) -> Option<MemInfo> {
+    let mut total = None;
+    let mut available = None;
+    let mut free = 0;
+    let mut buffers = 0;
+    let mut cached = 0;
+    let mut swap_total = 0;
+    let mut swap_free = 0;
+
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let Some(value) = value
+            .split_whitespace()
+            .next()
+            .and_then(|value| value.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        match key {
+            "MemTotal" => total = Some(value),
+            "MemAvailable" => available = Some(value),
+            "MemFree" => free = value,
+            "Buffers" => buffers = value,
+            "Cached" => cached = value,
+            "SwapTotal" => swap_total = value,
+            "SwapFree" => swap_free = value,
+            _ => {}
+        }
+    }
+
+    Some(MemInfo {
+        total: total?,
+        // Kernels older than 3.14 don't report MemAvailable
+        available: available.unwrap_or(free + buffers + cached),
+        swap_total,
+        swap_free,
+    })
+}
+
+pub fn parse_loadavg(contents: &str) -> Option<LoadAvg> {
+    let mut fields = contents.split_whitespace().map(|field| field.parse().ok());
+    Some(LoadAvg {
+        one: fields.next()??,
+        five: fields.next()??,
+        fifteen: fields.next()??,
+    })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Sample {
+    pub cpus: Vec<CpuTimes>,
+    pub memory: Option<MemInfo>,
+    pub load: Option<LoadAvg>,
+}
+
+impl Sample {
+    // Reads stat, meminfo and loadavg from the given procfs root
+    pub fn read(proc: &Path) -> Self {
+        let read = |name: &str| std::fs::read_to_string(proc.join(name)).unwrap_or_default();
+        Self {
+            cpus: parse_stat(&read("stat")),
+            memory: parse_meminfo(&read("meminfo")),
+            load: parse_loadavg(&read("loadavg")),
+        }
+    }
+}
+
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// Maps percentages to block characters, one per value
+pub fn sparkline(values: impl IntoIterator<Item = f32>) -> String {
+    values
+        .into_iter()
+        .map(|value| {
+            let index = (value.clamp(0.0, 100.0) / 100.0 * (SPARK_CHARS.len() - 1) as f32).round();
+            SPARK_CHARS[index as usize]
+        })
+        .collect()
+}
+
+// What the module shows, rounded the way it's shown, so readings that look the same compare
+// equal and don't cause a redraw
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reading {
+    // Total usage in whole percents
+    pub cpu: Option<f32>,
+    // Sparklines of each core's usage and of the total over time, empty when turned off
+    pub cores: String,
+    pub history: String,
+    pub core_count: usize,
+    // Used memory in whole percents
+    pub memory: Option<f32>,
+    // One minute load average, to two decimals
+    pub load: Option<f32>,
+}
+
+// Turns samples into readings, keeping the previous sample usage is measured against
+#[derive(Debug, Default)]
+pub struct Sampler {
+    previous: Option<Sample>,
+    history: VecDeque<f32>,
+}
+
+impl Sampler {
+    pub fn next(&mut self, sample: Sample, config: &Config) -> Reading {
+        // Without a previous sample there's no time span to measure, so usage starts at 0
+        let previous = self.previous.as_ref().unwrap_or(&sample);
+        let usage: Vec<f32> = sample
+            .cpus
+            .iter()
+            .zip(previous.cpus.iter())
+            .map(|(current, previous)| current.usage_since(previous))
+            .collect();
+
+        if let Some(total) = usage.first() {
+            self.history.push_back(*total);
+        }
+        while self.history.len() > config.history {
+            self.history.pop_front();
+        }
+
+        let reading = Reading {
+            cpu: usage.first().map(|total| total.round()),
+            cores: if config.per_core && usage.len() > 1 {
+                sparkline(usage[1..].iter().copied())
+            } else {
+                String::new()
+            },
+            history: sparkline(self.history.iter().copied()),
+            core_count: usage.len().saturating_sub(1),
+            memory: sample.memory.map(|memory| memory.used_percent().round()),
+            load: sample.load.map(|load| (load.one * 100.0).round() / 100.0),
+        };
+        self.previous = Some(sample);
+        reading
+    }
+}
+
+#[derive(Debug)]
+pub struct Sysinfo {
+    pub reading: Reading,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Reading(Reading),
+}
+
+impl Module for Sysinfo {
+    type Message = Message;
+
+    fn new() -> (Self, Task<Self::Message>) {
+        let reading =
+            Sampler::default().next(Sample::read(Path::new("/proc")), &config::get().sysinfo);
+        (Self { reading }, Task::none())
+    }
+
+    fn view(self: &Self) -> Element<'_, Self::Message> {
+        let config = config::get();
+        let config = &config.sysinfo;
+        let reading = &self.reading;
+        let mut items: Vec<Element<'_, Self::Message>> = vec![];
+
+        if let Some(total) = reading.cpu {
+            let level = Level::from_thresholds(total, config.cpu_warning, config.cpu_critical);
+            items.push(
+                text(format!("CPU {:>3.0}%", total))
+                    .style(level_style(level))
+                    .into(),
+            );
+            if !reading.cores.is_empty() {
+                items.push(text(reading.cores.clone()).into());
+            }
+            if !reading.history.is_empty() {
+                items.push(text(reading.history.clone()).into());
+            }
+        }
+
+        if let Some(used) = reading.memory {
+            let level = Level::from_thresholds(used, config.memory_warning, config.memory_critical);
+            items.push(
+                text(format!("MEM {:>3.0}%", used))
+                    .style(level_style(level))
+                    .into(),
+            );
+        }
+
+        if let Some(load) = reading.load {
+            let cores = reading.core_count.max(1) as f32;
+            let level =
+                Level::from_thresholds(load / cores, config.load_warning, config.load_critical);
+            items.push(
+                text(format!("LOAD {:.2}", load))
+                    .style(level_style(level))
+                    .into(),
+            );
+        }
+
+        row(items).spacing(6).into()
+    }
+
+    fn update(self: &mut Self, message: Self::Message) -> Task<Self::Message> {
+        use Message::*;
+        match message {
+            Reading(reading) => {
+                self.reading = reading;
+                Task::none()
+            }
+        }
+    }
+
+    fn subscription(self: &Self) -> iced::Subscription<Self::Message> {
+        let mut sampler = Sampler::default();
+        timer::poll_changes(
+            "sysinfo",
+            Duration::from_millis(config::get().sysinfo.interval.max(100)),
+            move || sampler.next(Sample::read(Path::new("/proc")), &config::get().sysinfo),
+        )
+        .map(Message::Reading)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/proc");
+        std::fs::read_to_string(path.join(name)).unwrap()
+    }
+
+    #[test]
+    fn parses_stat() {
+        let cpus = parse_stat(&fixture("stat"));
+        assert_eq!(cpus.len(), 5);
+        assert_eq!(
+            cpus[0],
+            CpuTimes {
+                idle: 14812053,
+                total: 16430430,
+            }
+        );
+        assert_eq!(
+            cpus[4],
+            CpuTimes {
+                idle: 3702478,
+                total: 4103202,
+            }
+        );
+    }
+
+    #[test]
+    fn skips_malformed_stat_lines() {
+        let cpus = parse_stat("cpu  1 2 3\ncpu0 1 x 3 4\ncpu1 1 2 3 4\n");
+        assert_eq!(cpus, vec![CpuTimes { idle: 4, total: 10 }]);
+    }
+
+    #[test]
+    fn parses_meminfo() {
+        let memory = parse_meminfo(&fixture("meminfo")).unwrap();
+        assert_eq!(
+            memory,
+            MemInfo {
+                total: 16262320,
+                available: 10875932,
+                swap_total: 8388604,
+                swap_free: 8126460,
+            }
+        );
+        assert!((memory.used_percent() - 33.122).abs() < 0.01);
+    }
+
+    #[test]
+    fn estimates_available_memory_without_memavailable() {
+        let memory = parse_meminfo(&fixture("meminfo-no-available")).unwrap();
+        assert_eq!(memory.total, 2048000);
+        assert_eq!(memory.available, 512000 + 128000 + 256000);
+        assert_eq!(memory.swap_total, 0);
+    }
+
+    #[test]
+    fn rejects_meminfo_without_total() {
+        assert_eq!(parse_meminfo("MemFree: 100 kB\n"), None);
+        assert_eq!(MemInfo::default().used_percent(), 0.0);
+    }
+
+    #[test]
+    fn parses_loadavg() {
+        assert_eq!(
+            parse_loadavg(&fixture("loadavg")),
+            Some(LoadAvg {
+                one: 0.52,
+                five: 0.58,
+                fifteen: 0.59,
+            })
+        );
+        assert_eq!(parse_loadavg("0.52 0.58"), None);
+        assert_eq!(parse_loadavg(""), None);
+    }
+
+    #[test]
+    fn reads_sample_from_proc_root() {
+        let sample =
+            Sample::read(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/proc"));
+        assert_eq!(sample.cpus.len(), 5);
+        assert!(sample.memory.is_some());
+        assert!(sample.load.is_some());
+    }
+
+    #[test]
+    fn computes_usage_between_samples() {
+        let previous = CpuTimes {
+            idle: 100,
+            total: 200,
+        };
+        let current = CpuTimes {
+            idle: 130,
+            total: 300,
+        };
+        assert_eq!(current.usage_since(&previous), 70.0);
+    }
+
+    #[test]
+    fn usage_is_zero_when_counters_stand_still() {
+        let sample = CpuTimes {
+            idle: 100,
+            total: 200,
+        };
+        assert_eq!(sample.usage_since(&sample), 0.0);
+    }
+
+    #[test]
+    fn usage_survives_counters_going_backwards() {
+        // Counters can go back after a cpu is hotplugged or the host is suspended
+        let previous = CpuTimes {
+            idle: 500,
+            total: 1000,
+        };
+        let current = CpuTimes {
+            idle: 100,
+            total: 200,
+        };
+        assert_eq!(current.usage_since(&previous), 0.0);
+
+        // Only idle going backwards counts the whole interval as busy
+        let current = CpuTimes {
+            idle: 400,
+            total: 1100,
+        };
+        assert_eq!(current.usage_since(&previous), 100.0);
+    }
+
+    fn sample(idle: u64, total: u64) -> Sample {
+        Sample {
+            cpus: vec![CpuTimes { idle, total }, CpuTimes { idle, total }],
+            memory: Some(MemInfo {
+                total: 1000,
+                available: 504,
+                ..MemInfo::default()
+            }),
+            load: Some(LoadAvg {
+                one: 0.523,
+                five: 0.5,
+                fifteen: 0.5,
+            }),
+        }
+    }
+
+    #[test]
+    fn rounds_readings_like_they_are_shown() {
+        let config = Config {
+            history: 3,
+            per_core: true,
+            ..Config::default()
+        };
+        let mut sampler = Sampler::default();
+
+        let first = sampler.next(sample(100, 200), &config);
+        assert_eq!(first.cpu, Some(0.0));
+        assert_eq!(first.core_count, 1);
+        assert_eq!(first.memory, Some(50.0));
+        assert_eq!(first.load, Some(0.52));
+
+        let busy = sampler.next(sample(130, 300), &config);
+        assert_eq!(busy.cpu, Some(70.0));
+        assert_eq!(busy.cores, sparkline([70.0]));
+        assert_eq!(busy.history, sparkline([0.0, 70.0]));
+
+        // Only the oldest samples fall out of the history
+        sampler.next(sample(160, 400), &config);
+        let last = sampler.next(sample(260, 500), &config);
+        assert_eq!(last.cpu, Some(0.0));
+        assert_eq!(last.history, sparkline([70.0, 70.0, 0.0]));
+    }
+
+    #[test]
+    fn idle_readings_compare_equal() {
+        let config = Config {
+            history: 2,
+            ..Config::default()
+        };
+        let mut sampler = Sampler::default();
+        sampler.next(sample(0, 0), &config);
+        // 0.4% and 0.2% busy both show as 0%
+        let first = sampler.next(sample(996, 1000), &config);
+        let second = sampler.next(sample(1994, 2000), &config);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn hides_turned_off_sparklines() {
+        let config = Config {
+            history: 0,
+            per_core: false,
+            ..Config::default()
+        };
+        let mut sampler = Sampler::default();
+        sampler.next(sample(100, 200), &config);
+        let reading = sampler.next(sample(130, 300), &config);
+        assert_eq!(reading.cores, "");
+        assert_eq!(reading.history, "");
+    }
+
+    #[test]
+    fn draws_sparkline() {
+        assert_eq!(sparkline([0.0, 50.0, 100.0, 150.0, -5.0]), "▁▅██▁");
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use cosmic::iced::Subscription;
+use cosmic::iced::futures::stream;
+
+// Time left until the next multiple of `period` since the epoch
+pub fn until_next_boundary(now: SystemTime, period: Duration) -> Duration {
+    let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let period_nanos = period.as_nanos().max(1);
+    let elapsed = since_epoch.as_nanos() % period_nanos;
+    Duration::from_nanos((period_nanos - elapsed) as u64)
+}
+
+// Ticks right after every multiple of `period` since the epoch, so a clock showing seconds
+// changes exactly when the second does. Every timezone offset is a whole number of minutes,
+// so this also lines up with local seconds and minutes.
+pub fn aligned(period: Duration) -> Subscription<SystemTime> {
+    Subscription::run_with_id(
+        ("aligned", period),
+        stream::unfold((), move |_| async move {
+            tokio::time::sleep(until_next_boundary(SystemTime::now(), period)).await;
+            Some((SystemTime::now(), ()))
+        }),
+    )
+}
+
+// Samples every `period` but only emits when the result differs from the last one,
+// so the bar isn't redrawn for readings that didn't change. The first sample is only
+// compared against, since modules read their own when they're created.
+pub fn poll_changes<T, F>(id: &'static str, period: Duration, sample: F) -> Subscription<T>
+where
+    T: PartialEq + Clone + Send + 'static,
+    F: FnMut() -> T + Send + 'static,
+{
+    Subscription::run_with_id(
+        (id, period),
+        stream::unfold(
+            (None, sample),
+            move |(previous, mut sample): (Option<T>, F)| async move {
+                let previous = previous.unwrap_or_else(|| sample());
+                loop {
+                    tokio::time::sleep(period).await;
+                    let value = sample();
+                    if value != previous {
+                        return Some((value.clone(), (Some(value), sample)));
+                    }
+                }
+            },
+        ),
+    )
+}
@@ -0,0 +1,151 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, RwLock};
+
+use cosmic::iced::futures::{SinkExt, StreamExt};
+use cosmic::iced::{self, Subscription};
+use inotify::{Inotify, WatchMask};
+use serde::Deserialize;
+
+use crate::bar::{brightness, caffeine, clock, custom, hwmon, sysinfo};
+use crate::{launcher, power};
+
+static CONFIG: LazyLock<RwLock<Arc<Config>>> = LazyLock::new(|| RwLock::new(Arc::new(load())));
+
+// Every section falls back to its defaults, so an empty (or missing) file is a valid config
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub sysinfo: sysinfo::Config,
+    pub hwmon: hwmon::Config,
+    pub brightness: brightness::Config,
+    pub clock: clock::Config,
+    pub caffeine: caffeine::Config,
+    pub power: power::Config,
+    pub launcher: launcher::Config,
+    pub custom: Vec<custom::Script>,
+}
+
+pub fn get() -> Arc<Config> {
+    CONFIG.read().unwrap().clone()
+}
+
+// Re-reads the config file, views and subscriptions pick it up the next time they run
+pub fn reload() {
+    *CONFIG.write().unwrap() = Arc::new(load());
+}
+
+// Emits whenever config.toml is written, created or moved into place
+pub fn subscription() -> Subscription<()> {
+    Subscription::run(|| {
+        iced::stream::channel(1, async |mut output| {
+            let path = path();
+            let (Some(directory), Some(file_name)) = (path.parent(), path.file_name()) else {
+                return;
+            };
+
+            let inotify = match Inotify::init() {
+                Ok(inotify) => inotify,
+                Err(error) => {
+                    dbg!(error);
+                    return;
+                }
+            };
+            // Editors usually replace the file instead of writing to it, so watch the directory
+            if let Err(error) = inotify.watches().add(
+                directory,
+                WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE,
+            ) {
+                dbg!(error);
+                return;
+            }
+
+            let mut events = match inotify.into_event_stream([0u8; 1024]) {
+                Ok(events) => events,
+                Err(error) => {
+                    dbg!(error);
+                    return;
+                }
+            };
+
+            while let Some(Ok(event)) = events.next().await {
+                if event.name.as_deref() == Some(file_name) {
+                    let _ = output.send(()).await;
+                }
+            }
+        })
+    })
+}
+
+pub fn path() -> PathBuf {
+    config_home().join("my-shell").join("config.toml")
+}
+
+fn config_home() -> PathBuf {
+    match std::env::var("XDG_CONFIG_HOME") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => {
+            let home_dir = std::env::var("HOME").unwrap();
+            Path::new(&home_dir).join(".config")
+        }
+    }
+}
+
+// $XDG_CONFIG_HOME followed by $XDG_CONFIG_DIRS, most important first
+pub fn config_dirs() -> Vec<PathBuf> {
+    std::iter::once(config_home())
+        .chain(
+            std::env::var("XDG_CONFIG_DIRS")
+                .unwrap_or("/etc/xdg".into())
+                .split(':')
+                .map(|path| PathBuf::from(path)),
+        )
+        .collect()
+}
+
+// $XDG_DATA_HOME followed by $XDG_DATA_DIRS, most important first
+pub fn data_dirs() -> Vec<PathBuf> {
+    std::iter::once(match std::env::var("XDG_DATA_HOME") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => {
+            let home_dir = std::env::var("HOME").unwrap();
+            Path::new(&home_dir).join(".local").join("share")
+        }
+    })
+    .chain(
+        std::env::var("XDG_DATA_DIRS")
+            .unwrap_or("/usr/local/share/:/usr/share/".into())
+            .split(':')
+            .map(|path| PathBuf::from(path)),
+    )
+    .collect()
+}
+
+// Where state that should survive restarts is kept, e.g. launcher history
+pub fn state_dir() -> PathBuf {
+    match std::env::var("XDG_STATE_HOME") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => {
+            let home_dir = std::env::var("HOME").unwrap();
+            Path::new(&home_dir).join(".local").join("state")
+        }
+    }
+    .join("my-shell")
+}
+
+fn load() -> Config {
+    let contents = match std::fs::read_to_string(path()) {
+        Ok(contents) => contents,
+        Err(_) => return Config::default(),
+    };
+
+    match toml::from_str::<Config>(&contents) {
+        Ok(mut config) => {
+            config.clock.validate();
+            config
+        }
+        Err(error) => {
+            eprintln!("Failed to parse {}: {}", path().display(), error);
+            Config::default()
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use tokio::sync::OnceCell;
+use zbus::Connection;
+
+static SYSTEM_BUS: OnceCell<Connection> = OnceCell::const_new();
+static SESSION_BUS: OnceCell<Connection> = OnceCell::const_new();
+
+// Shared connections, opened on first use and kept for the lifetime of the shell.
+// Some services (like screensaver inhibitors) tie state to the connection, so don't close them.
+pub async fn system_bus() -> zbus::Result<&'static Connection> {
+    SYSTEM_BUS.get_or_try_init(Connection::system).await
+}
+
+pub async fn session_bus() -> zbus::Result<&'static Connection> {
+    SESSION_BUS.get_or_try_init(Connection::session).await
+}
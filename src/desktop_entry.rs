@@ -0,0 +1,573 @@
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+// The group holding the entry itself, the others are actions
+const MAIN_GROUP: &str = "Desktop Entry";
+
+// The locale suffixes to look for, best first, from LC_ALL, LC_MESSAGES or LANG. For
+// de_DE.UTF-8@euro that's de_DE@euro, de_DE, de@euro and de, the encoding is ignored.
+static LOCALES: LazyLock<Vec<String>> = LazyLock::new(|| {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    if locale == "C" || locale == "POSIX" || locale.starts_with("C.") {
+        return Vec::new();
+    }
+
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale.as_str(), None),
+    };
+    let locale = locale.split('.').next().unwrap_or_default();
+    let (lang, country) = match locale.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (locale, None),
+    };
+    if lang.is_empty() {
+        return Vec::new();
+    }
+
+    let mut locales = Vec::new();
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        locales.push(format!("{}_{}@{}", lang, country, modifier));
+    }
+    if let Some(country) = country {
+        locales.push(format!("{}_{}", lang, country));
+    }
+    if let Some(modifier) = modifier {
+        locales.push(format!("{}@{}", lang, modifier));
+    }
+    locales.push(lang.to_string());
+    locales
+});
+
+// Undoes the escapes allowed in string values
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            // Anything else is kept as is, \; is handled when splitting lists
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+// Splits a list value on semicolons that aren't escaped
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(';') => item.push(';'),
+                Some(other) => {
+                    item.push('\\');
+                    item.push(other);
+                }
+                None => item.push('\\'),
+            },
+            ';' => items.push(std::mem::take(&mut item)),
+            _ => item.push(c),
+        }
+    }
+    if !item.is_empty() {
+        items.push(item);
+    }
+    items
+        .iter()
+        .map(|item| unescape(item))
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+// Splits an Exec value, already unescaped as a string, into arguments separated by spaces.
+// Inside double quotes a backslash escapes " ` $ and \. Each argument comes with whether
+// it was quoted, since field codes don't count inside quotes.
+fn split_exec(exec: &str) -> Result<Vec<(String, bool)>, String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut quoted = false;
+    let mut in_arg = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' if !quoted => {
+                if in_arg {
+                    args.push((std::mem::take(&mut arg), false));
+                    in_arg = false;
+                }
+            }
+            '"' if !quoted => {
+                if in_arg {
+                    return Err("Quotes must surround a whole argument".to_string());
+                }
+                quoted = true;
+                in_arg = true;
+            }
+            '"' => {
+                args.push((std::mem::take(&mut arg), true));
+                quoted = false;
+                in_arg = false;
+                // The closing quote has to end the argument
+                match chars.next() {
+                    None | Some(' ' | '\t' | '\n') => {}
+                    Some(_) => return Err("Quotes must surround a whole argument".to_string()),
+                }
+            }
+            '\\' if quoted => match chars.next() {
+                Some(escaped @ ('"' | '`' | '$' | '\\')) => arg.push(escaped),
+                Some(other) => {
+                    arg.push('\\');
+                    arg.push(other);
+                }
+                None => return Err("Unterminated quote".to_string()),
+            },
+            _ => {
+                arg.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if quoted {
+        return Err("Unterminated quote".to_string());
+    }
+    if in_arg {
+        args.push((arg, false));
+    }
+    Ok(args)
+}
+
+fn is_executable(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+// One [Desktop Action <id>] group of an entry
+#[derive(Debug, Clone)]
+pub struct Action {
+    pub id: String,
+    pub name: String,
+    pub icon: Option<String>,
+    pub exec: Option<String>,
+}
+
+// A parsed .desktop file, see
+// https://specifications.freedesktop.org/desktop-entry-spec/latest/
+#[derive(Debug, Clone, Default)]
+pub struct DesktopEntry {
+    // The desktop file ID, e.g. org.gnome.Nautilus.desktop, when loaded from an applications dir
+    pub id: String,
+    pub path: PathBuf,
+    // Keys by group name, with any locale kept in the key, e.g. "Name[de]"
+    groups: HashMap<String, HashMap<String, String>>,
+}
+
+impl DesktopEntry {
+    // Lenient, lines that don't parse are skipped instead of failing the whole file
+    pub fn parse(path: &Path, contents: &str) -> Self {
+        let mut groups: HashMap<String, HashMap<String, String>> = HashMap::new();
+        let mut group: Option<String> = None;
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                group = Some(name.to_string());
+                continue;
+            }
+            let (Some(group), Some((key, value))) = (&group, line.split_once('=')) else {
+                continue;
+            };
+            // The first occurrence of a key wins
+            groups
+                .entry(group.clone())
+                .or_default()
+                .entry(key.trim().to_string())
+                .or_insert(value.trim().to_string());
+        }
+
+        Self {
+            id: String::new(),
+            path: path.to_path_buf(),
+            groups,
+        }
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        Ok(Self::parse(path, &std::fs::read_to_string(path)?))
+    }
+
+    // The raw value of a key
+    pub fn get(&self, group: &str, key: &str) -> Option<&str> {
+        self.groups.get(group)?.get(key).map(String::as_str)
+    }
+
+    // The value for the user's locale, falling back to the untranslated one
+    pub fn get_localized(&self, group: &str, key: &str) -> Option<&str> {
+        LOCALES
+            .iter()
+            .find_map(|locale| self.get(group, &format!("{}[{}]", key, locale)))
+            .or_else(|| self.get(group, key))
+    }
+
+    pub fn string(&self, key: &str) -> Option<String> {
+        self.get(MAIN_GROUP, key).map(unescape)
+    }
+
+    pub fn localized_string(&self, key: &str) -> Option<String> {
+        self.get_localized(MAIN_GROUP, key).map(unescape)
+    }
+
+    pub fn boolean(&self, key: &str) -> bool {
+        self.get(MAIN_GROUP, key) == Some("true")
+    }
+
+    pub fn list(&self, key: &str) -> Vec<String> {
+        self.get(MAIN_GROUP, key)
+            .map(split_list)
+            .unwrap_or_default()
+    }
+
+    pub fn entry_type(&self) -> Option<&str> {
+        self.get(MAIN_GROUP, "Type")
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.localized_string("Name")
+    }
+
+    pub fn generic_name(&self) -> Option<String> {
+        self.localized_string("GenericName")
+    }
+
+    pub fn keywords(&self) -> Vec<String> {
+        self.get_localized(MAIN_GROUP, "Keywords")
+            .map(split_list)
+            .unwrap_or_default()
+    }
+
+    pub fn exec(&self) -> Option<String> {
+        self.string("Exec")
+    }
+
+    pub fn icon(&self) -> Option<String> {
+        self.string("Icon")
+    }
+
+    pub fn comment(&self) -> Option<String> {
+        self.localized_string("Comment")
+    }
+
+    // Whether OnlyShowIn and NotShowIn allow the entry on any of these desktops. The first
+    // desktop either list names decides, like XDG_CURRENT_DESKTOP's order means.
+    pub fn shown_in(&self, desktops: &[String]) -> bool {
+        let only_show_in = self.list("OnlyShowIn");
+        let not_show_in = self.list("NotShowIn");
+        let listed = |list: &Vec<String>, desktop: &String| {
+            list.iter().any(|name| name.eq_ignore_ascii_case(desktop))
+        };
+
+        for desktop in desktops {
+            if listed(&only_show_in, desktop) {
+                return true;
+            }
+            if listed(&not_show_in, desktop) {
+                return false;
+            }
+        }
+        only_show_in.is_empty()
+    }
+
+    // Whether the program TryExec names is installed, as an absolute path or in $PATH.
+    // Entries without TryExec pass.
+    pub fn try_exec(&self) -> bool {
+        let Some(program) = self.string("TryExec").filter(|program| !program.is_empty()) else {
+            return true;
+        };
+        if program.contains('/') {
+            return is_executable(Path::new(&program));
+        }
+        std::env::var("PATH")
+            .unwrap_or_default()
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .any(|dir| is_executable(&Path::new(dir).join(&program)))
+    }
+
+    // Turns an Exec value of this entry (or one of its actions) into the arguments to run,
+    // with the field codes replaced. `files` are paths or URLs to open, if any.
+    pub fn expand_exec(&self, exec: &str, files: &[String]) -> Result<Vec<String>, String> {
+        let mut argv = Vec::new();
+
+        for (arg, quoted) in split_exec(exec)? {
+            // Field codes in quotes are literal, but %% is still how a % is written
+            if quoted {
+                argv.push(arg.replace("%%", "%"));
+                continue;
+            }
+
+            // These expand to any number of arguments, so they have to stand alone
+            match arg.as_str() {
+                "%F" | "%U" => {
+                    argv.extend(files.iter().cloned());
+                    continue;
+                }
+                "%i" => {
+                    if let Some(icon) = self.icon() {
+                        argv.push("--icon".to_string());
+                        argv.push(icon);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            let mut expanded = String::new();
+            // An argument made only of codes that expanded to nothing is dropped
+            let mut literal = false;
+            let mut chars = arg.chars();
+            while let Some(c) = chars.next() {
+                if c != '%' {
+                    expanded.push(c);
+                    literal = true;
+                    continue;
+                }
+                match chars.next() {
+                    Some('%') => {
+                        expanded.push('%');
+                        literal = true;
+                    }
+                    // Apps that take one file get the first, like most launchers do
+                    Some('f' | 'u') => {
+                        if let Some(file) = files.first() {
+                            expanded.push_str(file);
+                        }
+                    }
+                    Some('c') => expanded.push_str(&self.name().unwrap_or_default()),
+                    Some('k') => expanded.push_str(&self.path.to_string_lossy()),
+                    // Deprecated codes, and ones that aren't allowed inside an argument
+                    Some(_) | None => {}
+                }
+            }
+            if literal || !expanded.is_empty() {
+                argv.push(expanded);
+            }
+        }
+
+        if argv.is_empty() {
+            return Err("Exec is empty".to_string());
+        }
+        Ok(argv)
+    }
+
+    // Actions listed in Actions= that have a group with a name
+    pub fn actions(&self) -> Vec<Action> {
+        self.list("Actions")
+            .into_iter()
+            .filter_map(|id| {
+                let group = format!("Desktop Action {}", id);
+                let name = self.get_localized(&group, "Name")?;
+                Some(Action {
+                    id,
+                    name: unescape(name),
+                    icon: self.get(&group, "Icon").map(unescape),
+                    exec: self.get(&group, "Exec").map(unescape),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(keys: &str) -> DesktopEntry {
+        DesktopEntry::parse(
+            Path::new("/usr/share/applications/app.desktop"),
+            &format!("[Desktop Entry]\nType=Application\nName=App\n{}", keys),
+        )
+    }
+
+    fn expand(exec: &str, files: &[&str]) -> Result<Vec<String>, String> {
+        let files: Vec<String> = files.iter().map(|file| file.to_string()).collect();
+        entry("Icon=app-icon").expand_exec(exec, &files)
+    }
+
+    fn args(exec: &str) -> Vec<String> {
+        expand(exec, &[]).unwrap()
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(args("app  -a\t--b"), ["app", "-a", "--b"]);
+        assert_eq!(args(" app "), ["app"]);
+    }
+
+    #[test]
+    fn keeps_quoted_arguments_together() {
+        assert_eq!(args(r#"app "two words" """#), ["app", "two words", ""]);
+    }
+
+    #[test]
+    fn unescapes_inside_quotes() {
+        assert_eq!(
+            args(r#"app "\"a\" \`b\` \$c \\d""#),
+            ["app", r#""a" `b` $c \d"#]
+        );
+        // Other backslashes are kept, and outside quotes they're literal
+        assert_eq!(args(r#"app "\a" \b"#), ["app", r"\a", r"\b"]);
+    }
+
+    #[test]
+    fn unescapes_the_string_first() {
+        // As written in the file: four backslashes are one in the argument, since the string
+        // escapes turn them into two before quoting turns those into one
+        let quoted = entry(r#"Exec=sh -c "echo \\\\ \"quoted\" \$HOME""#);
+        let exec = quoted.exec().unwrap();
+        assert_eq!(exec, r#"sh -c "echo \\ \"quoted\" \$HOME""#);
+        assert_eq!(
+            quoted.expand_exec(&exec, &[]).unwrap(),
+            ["sh", "-c", r#"echo \ "quoted" $HOME"#]
+        );
+
+        // \s is a space by then, so it separates arguments like one
+        let spaced = entry(r"Exec=app\sname --opt\tvalue");
+        assert_eq!(
+            spaced.expand_exec(&spaced.exec().unwrap(), &[]).unwrap(),
+            ["app", "name", "--opt", "value"]
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes() {
+        let error = Err("Unterminated quote".to_string());
+        assert_eq!(expand(r#"app "open"#, &[]), error);
+        assert_eq!(expand(r#"app "open\"#, &[]), error);
+    }
+
+    #[test]
+    fn rejects_quotes_inside_arguments() {
+        let error = Err("Quotes must surround a whole argument".to_string());
+        assert_eq!(expand(r#"app a"b""#, &[]), error);
+        assert_eq!(expand(r#"app "a"b"#, &[]), error);
+        assert_eq!(expand(r#"app "a""b""#, &[]), error);
+    }
+
+    #[test]
+    fn expands_single_files() {
+        assert_eq!(expand("app %f", &[]).unwrap(), ["app"]);
+        assert_eq!(expand("app %f", &["/a"]).unwrap(), ["app", "/a"]);
+        // Only the first, the app takes one at a time
+        assert_eq!(expand("app %f", &["/a", "/b"]).unwrap(), ["app", "/a"]);
+        assert_eq!(
+            expand("app %u", &["https://a.org", "https://b.org"]).unwrap(),
+            ["app", "https://a.org"]
+        );
+        assert_eq!(expand("app %u", &[]).unwrap(), ["app"]);
+    }
+
+    #[test]
+    fn expands_file_lists() {
+        assert_eq!(expand("app %F", &[]).unwrap(), ["app"]);
+        assert_eq!(expand("app %F", &["/a"]).unwrap(), ["app", "/a"]);
+        assert_eq!(
+            expand("app %F --end", &["/a", "/b c"]).unwrap(),
+            ["app", "/a", "/b c", "--end"]
+        );
+        assert_eq!(
+            expand("app %U", &["file:///a", "https://b.org"]).unwrap(),
+            ["app", "file:///a", "https://b.org"]
+        );
+        assert_eq!(expand("app %U", &[]).unwrap(), ["app"]);
+    }
+
+    #[test]
+    fn expands_codes_within_arguments() {
+        assert_eq!(
+            expand("app --file=%f", &["/a"]).unwrap(),
+            ["app", "--file=/a"]
+        );
+        // The rest of the argument stays without a file
+        assert_eq!(expand("app --file=%f", &[]).unwrap(), ["app", "--file="]);
+        // List codes only count on their own
+        assert_eq!(
+            expand("app --files=%F", &["/a"]).unwrap(),
+            ["app", "--files="]
+        );
+    }
+
+    #[test]
+    fn expands_the_icon() {
+        assert_eq!(args("app %i"), ["app", "--icon", "app-icon"]);
+        let entry = entry("");
+        assert_eq!(entry.expand_exec("app %i", &[]).unwrap(), ["app"]);
+    }
+
+    #[test]
+    fn expands_name_and_path() {
+        assert_eq!(args("app --title %c"), ["app", "--title", "App"]);
+        assert_eq!(
+            args("app %k"),
+            ["app", "/usr/share/applications/app.desktop"]
+        );
+    }
+
+    #[test]
+    fn expands_percent_signs() {
+        assert_eq!(args("app %% 100%%"), ["app", "%", "100%"]);
+    }
+
+    #[test]
+    fn drops_deprecated_codes() {
+        assert_eq!(args("app %d %D %n %N %v %m -x"), ["app", "-x"]);
+        // So does a lone % at the end
+        assert_eq!(args("app %"), ["app"]);
+    }
+
+    #[test]
+    fn keeps_codes_in_quotes_literal() {
+        assert_eq!(
+            expand(r#"app "%f" "%F" "%i" "100%%""#, &["/a"]).unwrap(),
+            ["app", "%f", "%F", "%i", "100%"]
+        );
+        assert_eq!(args(r#"sh -c "date +%%H""#), ["sh", "-c", "date +%H"]);
+    }
+
+    #[test]
+    fn rejects_exec_that_expands_to_nothing() {
+        let error = Err("Exec is empty".to_string());
+        assert_eq!(expand("", &[]), error);
+        assert_eq!(expand("   ", &[]), error);
+        assert_eq!(expand("%f", &[]), error);
+        assert_eq!(expand("%F %U", &[]), error);
+        assert_eq!(entry("").expand_exec("%i", &[]), error);
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use crate::config;
+use crate::desktop_entry::DesktopEntry;
+
+// The theme every other one falls back to
+const FALLBACK_THEME: &str = "hicolor";
+
+// Formats the shell can draw, in the order the spec prefers them
+const EXTENSIONS: [&str; 2] = ["png", "svg"];
+
+// Loaded themes by name, None if there's no such theme
+static THEMES: LazyLock<Mutex<HashMap<String, Option<Arc<Theme>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Looked up icons by theme, name, size and scale
+static ICONS: LazyLock<Mutex<HashMap<(String, String, u32, u32), Option<PathBuf>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// The desktop's icon theme, from GNOME's settings or else GTK's settings.ini. Looked up once,
+// since asking gsettings starts a process.
+static SYSTEM_THEME: LazyLock<Option<String>> = LazyLock::new(|| {
+    let gsettings = std::process::Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.interface", "icon-theme"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .trim()
+                .trim_matches('\'')
+                .to_string()
+        })
+        .filter(|theme| !theme.is_empty());
+
+    gsettings.or_else(|| {
+        config::config_dirs()
+            .into_iter()
+            .flat_map(|dir| [dir.join("gtk-4.0"), dir.join("gtk-3.0")])
+            .map(|dir| dir.join("settings.ini"))
+            .find_map(|path| {
+                let contents = std::fs::read_to_string(&path).ok()?;
+                DesktopEntry::parse(&path, &contents)
+                    .get("Settings", "gtk-icon-theme-name")
+                    .map(str::to_string)
+            })
+    })
+});
+
+// Where themes live, most important first
+fn base_dirs() -> Vec<PathBuf> {
+    let home = PathBuf::from(std::env::var("HOME").unwrap());
+    std::iter::once(home.join(".icons"))
+        .chain(config::data_dirs().into_iter().map(|dir| dir.join("icons")))
+        .collect()
+}
+
+fn theme_name() -> String {
+    config::get()
+        .launcher
+        .icon_theme
+        .clone()
+        .or(SYSTEM_THEME.clone())
+        .unwrap_or(FALLBACK_THEME.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+// One of the theme's size directories, e.g. 48x48/apps
+#[derive(Debug)]
+struct Directory {
+    size: u32,
+    scale: u32,
+    kind: Kind,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+}
+
+impl Directory {
+    fn parse(index: &DesktopEntry, name: &str) -> Option<Self> {
+        let number = |key: &str| index.get(name, key)?.trim().parse::<u32>().ok();
+        let size = number("Size")?;
+        Some(Self {
+            size,
+            scale: number("Scale").unwrap_or(1),
+            kind: match index.get(name, "Type") {
+                Some("Fixed") => Kind::Fixed,
+                Some("Scalable") => Kind::Scalable,
+                _ => Kind::Threshold,
+            },
+            min_size: number("MinSize").unwrap_or(size),
+            max_size: number("MaxSize").unwrap_or(size),
+            threshold: number("Threshold").unwrap_or(2),
+        })
+    }
+
+    fn matches_size(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+        match self.kind {
+            Kind::Fixed => self.size == size,
+            Kind::Scalable => (self.min_size..=self.max_size).contains(&size),
+            Kind::Threshold => (self.size.saturating_sub(self.threshold)
+                ..=self.size + self.threshold)
+                .contains(&size),
+        }
+    }
+
+    // How far off the directory's icons are from the wanted size, in pixels
+    fn size_distance(&self, size: u32, scale: u32) -> u32 {
+        let wanted = size * scale;
+        let (min, max) = match self.kind {
+            Kind::Fixed => (self.size, self.size),
+            Kind::Scalable => (self.min_size, self.max_size),
+            Kind::Threshold => (
+                self.size.saturating_sub(self.threshold),
+                self.size + self.threshold,
+            ),
+        };
+        let (min, max) = (min * self.scale, max * self.scale);
+        if wanted < min {
+            min - wanted
+        } else {
+            wanted.saturating_sub(max)
+        }
+    }
+}
+
+// An icon file, with where it was found for ranking duplicates like the spec's loop order does
+#[derive(Debug)]
+struct IconFile {
+    directory: usize,
+    base_dir: usize,
+    extension: usize,
+    path: PathBuf,
+}
+
+#[derive(Debug)]
+struct Theme {
+    inherits: Vec<String>,
+    directories: Vec<Directory>,
+    // Every icon in the theme by name, listed once when the theme loads so lookups don't have
+    // to check for files
+    icons: HashMap<String, Vec<IconFile>>,
+}
+
+impl Theme {
+    fn load(name: &str) -> Option<Self> {
+        let base_dirs = base_dirs();
+        let index_path = base_dirs
+            .iter()
+            .map(|dir| dir.join(name).join("index.theme"))
+            .find(|path| path.is_file())?;
+        let index = DesktopEntry::parse(&index_path, &std::fs::read_to_string(&index_path).ok()?);
+
+        let list = |key: &str| -> Vec<String> {
+            index
+                .get("Icon Theme", key)
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let (names, directories): (Vec<String>, Vec<Directory>) = list("Directories")
+            .into_iter()
+            .chain(list("ScaledDirectories"))
+            .filter_map(|dir| Some((dir.clone(), Directory::parse(&index, &dir)?)))
+            .unzip();
+
+        let mut icons: HashMap<String, Vec<IconFile>> = HashMap::new();
+        for (base_dir, base) in base_dirs.iter().enumerate() {
+            for (directory, dir_name) in names.iter().enumerate() {
+                let Ok(files) = std::fs::read_dir(base.join(name).join(dir_name)) else {
+                    continue;
+                };
+                for file in files.filter_map(|file| file.ok()) {
+                    let path = file.path();
+                    let (Some(stem), Some(extension)) = (path.file_stem(), path.extension()) else {
+                        continue;
+                    };
+                    let Some(extension) = EXTENSIONS.iter().position(|e| *e == extension) else {
+                        continue;
+                    };
+                    icons
+                        .entry(stem.to_string_lossy().into_owned())
+                        .or_default()
+                        .push(IconFile {
+                            directory,
+                            base_dir,
+                            extension,
+                            path,
+                        });
+                }
+            }
+        }
+
+        Some(Self {
+            inherits: list("Inherits"),
+            directories,
+            icons,
+        })
+    }
+
+    // An exact size match if there is one, otherwise the closest size
+    fn lookup(&self, icon: &str, size: u32, scale: u32) -> Option<PathBuf> {
+        let files = self.icons.get(icon)?;
+        let order = |file: &&IconFile| (file.directory, file.base_dir, file.extension);
+        files
+            .iter()
+            .filter(|file| self.directories[file.directory].matches_size(size, scale))
+            .min_by_key(order)
+            .or_else(|| {
+                files.iter().min_by_key(|file| {
+                    let distance = self.directories[file.directory].size_distance(size, scale);
+                    (distance, order(file))
+                })
+            })
+            .map(|file| file.path.clone())
+    }
+}
+
+fn theme(name: &str) -> Option<Arc<Theme>> {
+    if let Some(theme) = THEMES.lock().unwrap().get(name) {
+        return theme.clone();
+    }
+    // Loaded without holding the lock, so lookups in loaded themes don't wait on the disk. If
+    // two threads load the same theme, the first one to finish is kept.
+    let theme = Theme::load(name).map(Arc::new);
+    THEMES
+        .lock()
+        .unwrap()
+        .entry(name.to_string())
+        .or_insert(theme)
+        .clone()
+}
+
+// Searches the theme, then the ones it inherits from depth first
+fn lookup_in(
+    name: &str,
+    icon: &str,
+    size: u32,
+    scale: u32,
+    visited: &mut HashSet<String>,
+) -> Option<PathBuf> {
+    if !visited.insert(name.to_string()) {
+        return None;
+    }
+    let theme = theme(name)?;
+    theme.lookup(icon, size, scale).or_else(|| {
+        theme
+            .inherits
+            .iter()
+            .find_map(|parent| lookup_in(parent, icon, size, scale, visited))
+    })
+}
+
+// Icons outside any theme, like the ones apps put in /usr/share/pixmaps
+fn lookup_unthemed(icon: &str) -> Option<PathBuf> {
+    base_dirs()
+        .into_iter()
+        .chain(std::iter::once(PathBuf::from("/usr/share/pixmaps")))
+        .flat_map(|dir| EXTENSIONS.map(|extension| dir.join(format!("{}.{}", icon, extension))))
+        .find(|path| path.is_file())
+}
+
+// The file for an Icon= value at a size in logical pixels, which can also be an absolute path,
+// see https://specifications.freedesktop.org/icon-theme-spec/latest/
+// Results are cached, including missing icons.
+pub fn lookup(icon: &str, size: u32, scale: u32) -> Option<PathBuf> {
+    if icon.starts_with('/') {
+        let path = Path::new(icon);
+        return path.is_file().then(|| path.to_path_buf());
+    }
+
+    let theme_name = theme_name();
+    let key = (theme_name.clone(), icon.to_string(), size, scale);
+    if let Some(path) = ICONS.lock().unwrap().get(&key) {
+        return path.clone();
+    }
+
+    let mut visited = HashSet::new();
+    let path = lookup_in(&theme_name, icon, size, scale, &mut visited)
+        .or_else(|| lookup_in(FALLBACK_THEME, icon, size, scale, &mut visited))
+        .or_else(|| lookup_unthemed(icon));
+    ICONS.lock().unwrap().insert(key, path.clone());
+    path
+}
+
+// Loads the desktop's theme and everything it inherits from ahead of the first lookup, since
+// that reads every icon directory and may ask gsettings for the theme's name
+pub fn preload() {
+    let mut visited = HashSet::new();
+    let mut pending = vec![theme_name(), FALLBACK_THEME.to_string()];
+    while let Some(name) = pending.pop() {
+        if !visited.insert(name.clone()) {
+            continue;
+        }
+        if let Some(theme) = theme(&name) {
+            pending.extend(theme.inherits.iter().cloned());
+        }
+    }
+}
@@ -1,5 +1,9 @@
-use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::path::PathBuf;
 
+use cosmic::applet::token::subscription::{
+    TokenRequest, TokenUpdate, activation_token_subscription,
+};
 use cosmic::cctk;
 use cosmic::cctk::sctk::shell::wlr_layer::Anchor;
 use cosmic::iced::event::wayland::LayerEvent;
@@ -17,25 +21,106 @@ use cosmic::iced_winit::commands::subsurface::KeyboardInteractivity;
 use cosmic::widget::Space;
 use hyprland::dispatch;
 use hyprland::dispatch::Dispatch;
-use hyprland::dispatch::DispatchType;
+use hyprland::dispatch::{DispatchType, WindowIdentifier};
 use iced::border::radius;
 use iced::widget::{container, text};
 use iced::{Border, Element, Length, Task, Theme, window};
-use walkdir::WalkDir;
-use xdg_desktop_entries::{ApplicationDesktopEntry, DesktopEntryType};
-
-use rust_fuzzy_search::fuzzy_compare;
+use serde::Deserialize;
 
-use crate::ShellMessage;
 use crate::window::Window;
+use crate::{ShellMessage, config, icon_theme};
+use provider::{Action, LauncherItem, SearchProvider};
+
+pub mod apps;
+pub mod calculator;
+pub mod command;
+pub mod files;
+mod glob;
+pub mod history;
+pub mod launch;
+mod matcher;
+pub mod mime;
+pub mod provider;
+pub mod windows;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Input starting with this is run as a shell command
+    pub command_prefix: String,
+    // Used for Shift+Enter on commands and Terminal=true apps, falls back to $TERMINAL and
+    // then xterm
+    pub terminal: Option<String>,
+    // Input starting with this searches file names
+    pub file_prefix: String,
+    // Directories to index, ~ is expanded
+    pub file_roots: Vec<String>,
+    // Patterns in .gitignore syntax, relative to each root
+    pub file_excludes: Vec<String>,
+    // Whether to index dotfiles and the contents of dot directories
+    pub file_hidden: bool,
+    // Stops indexing after this many files
+    pub file_limit: usize,
+    // Seconds before the index is rebuilt when the launcher opens
+    pub file_reindex_interval: u64,
+    // Input starting with this lists open windows
+    pub window_prefix: String,
+    // Puts launched programs in their own systemd user scope
+    pub systemd_scopes: bool,
+    // Desktop names matched against OnlyShowIn and NotShowIn, instead of XDG_CURRENT_DESKTOP
+    pub desktops: Vec<String>,
+    // Ranks apps launched often and lately higher. Shift+Delete forgets the selected one.
+    pub history: bool,
+    // Desktop file IDs without .desktop that are never remembered, e.g. "firefox"
+    pub history_exclude: Vec<String>,
+    // The icon theme for results, instead of the one GNOME or GTK settings name
+    pub icon_theme: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            command_prefix: ">".to_string(),
+            terminal: None,
+            file_prefix: "/".to_string(),
+            file_roots: vec!["~".to_string()],
+            file_excludes: vec![
+                "node_modules/".to_string(),
+                "target/".to_string(),
+                "__pycache__/".to_string(),
+            ],
+            file_hidden: false,
+            file_limit: 200_000,
+            file_reindex_interval: 600,
+            window_prefix: "@".to_string(),
+            systemd_scopes: true,
+            desktops: Vec::new(),
+            history: true,
+            history_exclude: Vec::new(),
+            icon_theme: None,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Launcher {
     pub window: Option<window::Id>,
     pub input: String,
-    pub results: Vec<ApplicationDesktopEntry>, // TODO: Change this to be a generic action, so i can do math and stuff too
-    pub apps: Vec<ApplicationDesktopEntry>,
+    pub results: Vec<LauncherItem>,
+    pub providers: Vec<Box<dyn SearchProvider>>,
     pub selected_item: usize,
+    // Tracked so Submit can tell Enter from Shift+Enter
+    pub modifiers: keyboard::Modifiers,
+    // Where activation tokens are requested, once the token subscription is running
+    pub token_requests: Option<cctk::sctk::reexports::calloop::channel::Sender<TokenRequest>>,
+    // Launches waiting for their token, by the key their request was sent with
+    pub pending_launches: HashMap<String, launch::Launch>,
+    pub launch_count: u64,
+    // The launcher surface's scale, rounded up to the icon sizes themes have
+    pub scale: u32,
+    // Icon files for the shown results by icon name, looked up when the results change since a
+    // lookup may read the disk
+    pub icons: HashMap<String, Option<PathBuf>>,
 }
 
 #[derive(Debug, Clone)]
@@ -46,10 +131,116 @@ pub enum Message {
     Close,
     SelectionUp,
     SelectionDown,
+    Complete,
+    Remove,
+    ModifiersChanged(keyboard::Modifiers),
     LayerFocused(window::Id),
+    ScaleFactor(f32),
+    Token(TokenUpdate),
+    Notified(Result<(), String>),
+    Scoped(Result<(), String>),
     ShellMessage(Box<ShellMessage>),
 }
 
+impl Launcher {
+    fn item<'a>(&'a self, item: &'a LauncherItem) -> Element<'a, Message> {
+        row![
+            item.icon.as_ref().map(|icon| {
+                // Looked up by name through libcosmic when the theme doesn't have it
+                let handle = match self.icons.get(icon).cloned().flatten() {
+                    Some(path) => cosmic::widget::icon::from_path(path),
+                    None => cosmic::widget::icon::from_name(icon.as_str()).handle(),
+                };
+                cosmic::widget::icon::icon(handle).size(32)
+            }),
+            column![
+                Launcher::title(item),
+                item.subtitle
+                    .as_ref()
+                    .map(|subtitle| text(subtitle).size(14).wrapping(text::Wrapping::None)),
+            ],
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center)
+        .into()
+    }
+
+    // Looks up the icons of results that don't have theirs yet, at the surface's scale
+    fn resolve_icons(&mut self) {
+        for icon in self.results.iter().filter_map(|item| item.icon.as_ref()) {
+            if !self.icons.contains_key(icon) {
+                let path = icon_theme::lookup(icon, 32, self.scale);
+                self.icons.insert(icon.clone(), path);
+            }
+        }
+    }
+
+    // The title with the characters the query matched in bold
+    fn title(item: &LauncherItem) -> Element<'_, Message> {
+        let span = |run: String, bold: bool| {
+            let span = iced::widget::span(run);
+            if bold {
+                span.font(cosmic::font::bold())
+            } else {
+                span
+            }
+        };
+
+        let mut spans: Vec<text::Span<'_>> = Vec::new();
+        let mut run = String::new();
+        let mut bold = false;
+        for (i, c) in item.title.chars().enumerate() {
+            let highlighted = item.highlights.contains(&i);
+            if highlighted != bold && !run.is_empty() {
+                spans.push(span(std::mem::take(&mut run), bold));
+            }
+            bold = highlighted;
+            run.push(c);
+        }
+        if !run.is_empty() {
+            spans.push(span(run, bold));
+        }
+
+        iced::widget::rich_text(spans)
+            .size(20)
+            .wrapping(text::Wrapping::WordOrGlyph)
+            .into()
+    }
+
+    // Apps that want startup notification wait for an activation token, the rest start now
+    fn launch(&mut self, launch: launch::Launch) -> Task<Message> {
+        if launch.startup_notify {
+            if let Some(token_requests) = &self.token_requests {
+                // The key comes back in the token's exec field
+                self.launch_count += 1;
+                let key = self.launch_count.to_string();
+                let request = TokenRequest {
+                    app_id: "my-shell".to_string(),
+                    exec: key.clone(),
+                };
+                if token_requests.send(request).is_ok() {
+                    self.pending_launches.insert(key, launch);
+                    return Task::none();
+                }
+            }
+        }
+        Self::spawn(launch, None)
+    }
+
+    fn spawn(launch: launch::Launch, token: Option<&str>) -> Task<Message> {
+        match launch::spawn(&launch, token) {
+            Ok(pid) if config::get().launcher.systemd_scopes => {
+                Task::perform(launch::move_to_scope(launch.id, pid), Message::Scoped)
+            }
+            Ok(_) => Task::none(),
+            Err(error) => Task::perform(
+                launch::notify_failure(launch.name, error.to_string()),
+                Message::Notified,
+            ),
+        }
+    }
+}
+
 impl Window for Launcher {
     type Message = Message;
 
@@ -59,8 +250,20 @@ impl Window for Launcher {
                 window: None,
                 input: "".to_string(),
                 results: Vec::new(),
-                apps: Vec::new(),
+                providers: vec![
+                    Box::new(calculator::Calculator::default()),
+                    Box::new(command::Commands::new()),
+                    Box::new(files::Files::default()),
+                    Box::new(windows::Windows::default()),
+                    Box::new(apps::Apps::new()),
+                ],
                 selected_item: 0,
+                modifiers: keyboard::Modifiers::default(),
+                token_requests: None,
+                pending_launches: HashMap::new(),
+                launch_count: 0,
+                scale: 1,
+                icons: HashMap::new(),
             },
             Task::none(),
         )
@@ -107,30 +310,27 @@ impl Window for Launcher {
                                         let mut items = vec![];
                                         for i in 0..show_count {
                                             items.push({
-                                                let mut item = container(
-                                                    text(&self.results[i].name)
-                                                        .size(20)
-                                                        .wrapping(text::Wrapping::WordOrGlyph),
-                                                )
-                                                .center_y(Length::Fill)
-                                                .padding(10)
-                                                .width(Length::Fill)
-                                                .style(|theme: &Theme| container::Style {
-                                                    border: Border {
-                                                        radius: radius(20),
-                                                        width: 0.0,
-                                                        ..Default::default()
-                                                    },
-                                                    background: Some(
-                                                        theme
-                                                            .extended_palette()
-                                                            .background
-                                                            .weak
-                                                            .color
-                                                            .into(),
-                                                    ),
-                                                    ..Default::default()
-                                                });
+                                                let mut item =
+                                                    container(self.item(&self.results[i]))
+                                                        .center_y(Length::Fill)
+                                                        .padding(10)
+                                                        .width(Length::Fill)
+                                                        .style(|theme: &Theme| container::Style {
+                                                            border: Border {
+                                                                radius: radius(20),
+                                                                width: 0.0,
+                                                                ..Default::default()
+                                                            },
+                                                            background: Some(
+                                                                theme
+                                                                    .extended_palette()
+                                                                    .background
+                                                                    .weak
+                                                                    .color
+                                                                    .into(),
+                                                            ),
+                                                            ..Default::default()
+                                                        });
                                                 if i == self.selected_item {
                                                     item = item.style(|theme: &Theme| {
                                                         container::Style {
@@ -220,60 +420,66 @@ impl Window for Launcher {
             Input(input) => {
                 self.input = input;
 
-                let mut searched_apps: Vec<(&ApplicationDesktopEntry, f32)> = self
-                    .apps
-                    .iter()
-                    .filter_map(|app| {
-                        let similarity = fuzzy_compare(
-                            app.name.to_lowercase().as_str(),
-                            self.input.to_lowercase().as_str(),
-                        );
-                        if similarity == 0.0 {
-                            return None;
-                        }
-
-                        Some((app, similarity))
-                    })
-                    .collect();
-
-                searched_apps.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-
                 self.results.clear();
                 if self.input.len() != 0 {
-                    let count = std::cmp::min(searched_apps.len(), 5);
-                    self.selected_item = std::cmp::min(self.selected_item, count);
-
-                    for i in 0..count {
-                        self.results.push(searched_apps[i].0.clone())
-                    }
+                    self.results = provider::search(&self.providers, &self.input);
+                    self.results.truncate(5);
+                    self.selected_item =
+                        std::cmp::min(self.selected_item, self.results.len().saturating_sub(1));
                 }
+                self.resolve_icons();
 
                 Task::none()
             }
             Submit => {
-                if let Some(command) = &self.results[self.selected_item].exec {
-                    let re = regex::Regex::new("%(?<code>.)").unwrap();
+                let item = self.results[self.selected_item].clone();
+                for provider in self.providers.iter_mut() {
+                    provider.activated(&item, &self.input);
+                }
 
-                    let replaced_command = re.replace_all(command, |captures: &regex::Captures| {
-                        let ch = &captures["code"];
-                        match ch {
-                            "f" => "",  // TODO: Implement somehow?
-                            "F" => "",  // TODO: Implement somehow?
-                            "u" => "",  // TODO: Implement somehow?
-                            "U" => "",  // TODO: Implement somehow?
-                            "i" => "",  // TODO: Implement somehow?
-                            "c" => "",  // TODO: Implement somehow?
-                            "k" => "",  // TODO: Implement somehow?
-                            "%" => "%", // Replace %% with %.
-                            _ => "",
-                        }
-                    });
-                    let _ = dispatch!(Exec, &replaced_command);
-                    Task::done(Close)
-                } else {
-                    Task::none()
+                match item.action {
+                    Action::Launch(launch) => {
+                        Task::batch(vec![self.launch(launch), Task::done(Close)])
+                    }
+                    Action::Copy(contents) => {
+                        Task::batch(vec![iced::clipboard::write(contents), Task::done(Close)])
+                    }
+                    Action::Command(command) => {
+                        let launch = command::launch(&command, self.modifiers.shift());
+                        Task::batch(vec![self.launch(launch), Task::done(Close)])
+                    }
+                    Action::Open(path) => {
+                        Task::batch(vec![self.launch(mime::open(&path)), Task::done(Close)])
+                    }
+                    Action::Focus(address) => {
+                        let _ = dispatch!(FocusWindow, WindowIdentifier::Address(address));
+                        Task::done(Close)
+                    }
                 }
             }
+            Remove => {
+                if self.window.is_none() {
+                    return Task::none();
+                }
+                let Some(item) = self.results.get(self.selected_item).cloned() else {
+                    return Task::none();
+                };
+                if let Action::Focus(address) = item.action {
+                    let _ = dispatch!(CloseWindow, WindowIdentifier::Address(address));
+                    // The window may take a moment to go away, so drop it instead of searching
+                    // again
+                    self.results.remove(self.selected_item);
+                    self.selected_item =
+                        self.selected_item.min(self.results.len().saturating_sub(1));
+                    return Task::none();
+                }
+
+                // Anything else loses its history, and falls back to where it ranks without it
+                for provider in self.providers.iter_mut() {
+                    provider.forget(&item);
+                }
+                self.update(Input(self.input.clone()))
+            }
             Open => {
                 if self.window.is_some() {
                     return Task::done(Close);
@@ -294,58 +500,11 @@ impl Window for Launcher {
                 self.window = Some(id);
                 self.input = "".to_string();
                 self.results.clear();
-                self.apps.clear();
                 self.selected_item = 0;
 
-                // Fill launcher appps vec
-                // hm
-
-                self.apps.extend(
-                    std::iter::once(match std::env::var("XDG_DATA_HOME") {
-                        Ok(dir) => PathBuf::from(dir),
-                        Err(_) => {
-                            let home_dir = std::env::var("HOME").unwrap();
-                            Path::new(&home_dir).join(".local").join("share")
-                        }
-                    })
-                    .chain(
-                        std::env::var("XDG_DATA_DIRS")
-                            .unwrap_or("/usr/local/share/:/usr/share/".into())
-                            .split(':')
-                            .map(|path| PathBuf::from(path)),
-                    )
-                    .map(|path| path.join("applications"))
-                    .filter(|path| path.exists())
-                    .map(|path| {
-                        WalkDir::new(path)
-                            .follow_links(true)
-                            .into_iter()
-                            .filter_map(|entry| entry.ok())
-                            .filter(|entry| {
-                                entry.file_type().is_file()
-                                    && entry.path().extension().is_some_and(|e| e == "desktop")
-                            })
-                    })
-                    .flatten()
-                    .map(|entry| xdg_desktop_entries::parse_desktop_entry(entry.path()))
-                    .filter_map(|value| match value {
-                        Ok(entry) => {
-                            if let DesktopEntryType::Application(app_entry) = entry {
-                                if app_entry.no_display.is_some_and(|b| b) {
-                                    return None;
-                                }
-                                if app_entry.hidden.is_some_and(|b| b) {
-                                    return None;
-                                }
-                                // TODO: Handle OnlyShowIn, NotShowIn, TryExec
-                                Some(app_entry)
-                            } else {
-                                None
-                            }
-                        }
-                        Err(_) => None,
-                    }),
-                );
+                for provider in self.providers.iter_mut() {
+                    provider.refresh();
+                }
                 layer_shell_task
             }
             Close => {
@@ -375,31 +534,103 @@ impl Window for Launcher {
 
                 Task::none()
             }
+            Complete => {
+                if self.window.is_none() {
+                    return Task::none();
+                }
+                // Fills in the selected command, so arguments can be typed after it
+                let Some(Action::Command(command)) = self
+                    .results
+                    .get(self.selected_item)
+                    .map(|item| &item.action)
+                else {
+                    return Task::none();
+                };
+                let input = format!("{}{} ", config::get().launcher.command_prefix, command);
+                Task::batch(vec![
+                    self.update(Input(input)),
+                    text_input::move_cursor_to_end("launcher"),
+                ])
+            }
+            ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers;
+                Task::none()
+            }
             LayerFocused(layer_id) => match self.window {
-                Some(id) if id == layer_id => text_input::focus("launcher"),
+                // The surface is on an output by now, so its scale is known
+                Some(id) if id == layer_id => Task::batch(vec![
+                    text_input::focus("launcher"),
+                    window::get_scale_factor(id).map(ScaleFactor),
+                ]),
                 _ => Task::none(),
             },
+            ScaleFactor(scale_factor) => {
+                let scale = (scale_factor.ceil() as u32).max(1);
+                if scale != self.scale {
+                    self.scale = scale;
+                    self.icons.clear();
+                    self.resolve_icons();
+                }
+                Task::none()
+            }
+            Token(TokenUpdate::Init(token_requests)) => {
+                self.token_requests = Some(token_requests);
+                Task::none()
+            }
+            Token(TokenUpdate::Finished) => {
+                self.token_requests = None;
+                // Nothing will answer the waiting launches now, so start them without a token
+                let pending: Vec<launch::Launch> = self
+                    .pending_launches
+                    .drain()
+                    .map(|(_, launch)| launch)
+                    .collect();
+                Task::batch(pending.into_iter().map(|launch| Self::spawn(launch, None)))
+            }
+            Token(TokenUpdate::ActivationToken { token, exec, .. }) => {
+                match self.pending_launches.remove(&exec) {
+                    Some(launch) => Self::spawn(launch, token.as_deref()),
+                    None => Task::none(),
+                }
+            }
+            Notified(Ok(())) | Scoped(Ok(())) => Task::none(),
+            Notified(Err(error)) | Scoped(Err(error)) => {
+                dbg!(error);
+                Task::none()
+            }
             ShellMessage(_) => Task::none(),
         };
     }
 
     fn subscription(self: &Self) -> iced::Subscription<Self::Message> {
-        event::listen_with(|event, status, _id: window::Id| match event {
-            Event::Keyboard(keyboard::Event::KeyPressed { key, .. }) => match key {
-                Key::Named(Named::Escape) => Some(Message::Close),
-                Key::Named(Named::ArrowUp) => Some(Message::SelectionUp),
-                Key::Named(Named::ArrowDown) => Some(Message::SelectionDown),
+        iced::Subscription::batch(vec![
+            activation_token_subscription(0).map(Message::Token),
+            event::listen_with(|event, status, _id: window::Id| match event {
+                Event::Keyboard(keyboard::Event::KeyPressed {
+                    key: Key::Named(Named::Delete),
+                    modifiers,
+                    ..
+                }) if modifiers.shift() => Some(Message::Remove),
+                Event::Keyboard(keyboard::Event::KeyPressed { key, .. }) => match key {
+                    Key::Named(Named::Escape) => Some(Message::Close),
+                    Key::Named(Named::ArrowUp) => Some(Message::SelectionUp),
+                    Key::Named(Named::ArrowDown) => Some(Message::SelectionDown),
+                    Key::Named(Named::Tab) => Some(Message::Complete),
+                    _ => None,
+                },
+                Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                    Some(Message::ModifiersChanged(modifiers))
+                }
+                Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                    if status == Status::Ignored =>
+                {
+                    Some(Message::Close)
+                }
+                Event::PlatformSpecific(event::PlatformSpecific::Wayland(
+                    event::wayland::Event::Layer(LayerEvent::Focused, _, id),
+                )) => Some(Message::LayerFocused(id)),
                 _ => None,
-            },
-            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
-                if status == Status::Ignored =>
-            {
-                Some(Message::Close)
-            }
-            Event::PlatformSpecific(event::PlatformSpecific::Wayland(
-                event::wayland::Event::Layer(LayerEvent::Focused, _, id),
-            )) => Some(Message::LayerFocused(id)),
-            _ => None,
-        })
+            }),
+        ])
     }
 }
@@ -0,0 +1,392 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use inotify::{Inotify, WatchDescriptor, WatchMask};
+use walkdir::WalkDir;
+
+use super::history::History;
+use super::launch::Launch;
+use super::matcher::fuzzy_match;
+use super::provider::{Action, LauncherItem, SearchProvider};
+use crate::desktop_entry::DesktopEntry;
+use crate::{config, icon_theme};
+
+// The applications directories of every XDG data dir, most important first. Some may not
+// exist (yet).
+pub fn application_dirs() -> Vec<PathBuf> {
+    config::data_dirs()
+        .into_iter()
+        .map(|path| path.join("applications"))
+        .collect()
+}
+
+// Desktop files below an applications dir with their desktop file IDs, which are the paths
+// relative to it with / replaced by -, e.g. kde/konsole.desktop is kde-konsole.desktop
+fn desktop_files_in(dir: &Path) -> Vec<(String, PathBuf)> {
+    WalkDir::new(dir)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry.file_type().is_file() && entry.path().extension().is_some_and(|e| e == "desktop")
+        })
+        .filter_map(|entry| {
+            let id = entry
+                .path()
+                .strip_prefix(dir)
+                .ok()?
+                .to_string_lossy()
+                .replace('/', "-");
+            Some((id, entry.into_path()))
+        })
+        .collect()
+}
+
+// Every desktop file ID with the file it resolves to. Earlier dirs take precedence, so a
+// file in ~/.local/share/applications replaces the system one with the same ID.
+pub fn desktop_files() -> Vec<(String, PathBuf)> {
+    let mut seen = HashSet::new();
+    application_dirs()
+        .iter()
+        .flat_map(|dir| desktop_files_in(dir))
+        .filter(|(id, _)| seen.insert(id.clone()))
+        .collect()
+}
+
+// The file a desktop file ID resolves to
+pub fn desktop_file(id: &str) -> Option<PathBuf> {
+    application_dirs().into_iter().find_map(|dir| {
+        let path = dir.join(id);
+        if path.is_file() {
+            return Some(path);
+        }
+        // IDs with dashes may also come from subdirectories
+        desktop_files_in(&dir)
+            .into_iter()
+            .find(|(file_id, _)| file_id == id)
+            .map(|(_, path)| path)
+    })
+}
+
+// Loads a desktop file, keeping its ID
+pub fn load(id: String, path: &Path) -> std::io::Result<DesktopEntry> {
+    Ok(DesktopEntry {
+        id,
+        ..DesktopEntry::load(path)?
+    })
+}
+
+// The desktops entries are shown for, from the config or else XDG_CURRENT_DESKTOP
+fn current_desktops() -> Vec<String> {
+    let configured = &config::get().launcher.desktops;
+    if !configured.is_empty() {
+        return configured.clone();
+    }
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|desktop| !desktop.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// What launches are remembered as, e.g. firefox or firefox#new-window
+fn history_id(launch: &Launch) -> String {
+    match &launch.action {
+        Some(action) => format!("{}#{}", launch.id, action),
+        None => launch.id.clone(),
+    }
+}
+
+// A desktop action, searchable as e.g. "Firefox: New Private Window"
+#[derive(Debug, Clone)]
+struct AppAction {
+    title: String,
+    icon: Option<String>,
+    launch: Launch,
+}
+
+// An app with what searching it needs worked out ahead, so searches don't touch the entries
+#[derive(Debug, Clone)]
+struct App {
+    name: String,
+    // The untranslated name, people often know apps by it
+    untranslated_name: Option<String>,
+    generic_name: Option<String>,
+    keywords: Vec<String>,
+    comment: Option<String>,
+    icon: Option<String>,
+    // None when the Exec line is missing or broken, the actions may still work
+    launch: Option<Launch>,
+    actions: Vec<AppAction>,
+}
+
+impl App {
+    fn new(entry: &DesktopEntry) -> Self {
+        let name = entry.name().unwrap_or_default();
+        let icon = entry.icon();
+        let actions = entry
+            .actions()
+            .into_iter()
+            .filter_map(|action| {
+                let launch = Launch {
+                    action: Some(action.id),
+                    ..Launch::from_entry(entry, &action.exec?, &[]).ok()?
+                };
+                Some(AppAction {
+                    title: format!("{}: {}", name, action.name),
+                    icon: action.icon.or(icon.clone()),
+                    launch,
+                })
+            })
+            .collect();
+
+        Self {
+            untranslated_name: entry
+                .string("Name")
+                .filter(|untranslated| *untranslated != name),
+            generic_name: entry.generic_name(),
+            keywords: entry.keywords(),
+            comment: entry.comment(),
+            launch: entry
+                .exec()
+                .and_then(|exec| Launch::from_entry(entry, &exec, &[]).ok()),
+            name,
+            icon,
+            actions,
+        }
+    }
+
+    // How well the app matches, by its best field. Other fields count less than the name, since
+    // they describe what an app is rather than which one it is. Comes with the characters of the
+    // name that matched, if it did.
+    fn score(&self, query: &str) -> (f32, Vec<usize>) {
+        let compare = |text: &str| fuzzy_match(text, query).map_or(0.0, |found| found.score);
+        let (name, highlights) = match fuzzy_match(&self.name, query) {
+            Some(found) => (found.score, found.positions),
+            None => (0.0, Vec::new()),
+        };
+        let name = name.max(self.untranslated_name.as_deref().map_or(0.0, compare));
+        let generic_name = self.generic_name.as_deref().map_or(0.0, compare);
+        let keywords = self
+            .keywords
+            .iter()
+            .map(|keyword| compare(keyword))
+            .fold(0.0, f32::max);
+        let comment = self.comment.as_deref().map_or(0.0, compare);
+
+        let score = name
+            .max(generic_name * 0.8)
+            .max(keywords * 0.75)
+            .max(comment * 0.5);
+        (score, highlights)
+    }
+}
+
+// Loads every app that should be shown
+fn index() -> Vec<App> {
+    let desktops = current_desktops();
+    // Only the winning file of each ID is looked at, so Hidden=true in an override hides the
+    // app entirely
+    desktop_files()
+        .into_iter()
+        .filter_map(|(id, path)| load(id, &path).ok())
+        .filter(|entry| {
+            if entry.entry_type() != Some("Application") || entry.name().is_none() {
+                return false;
+            }
+            if entry.boolean("NoDisplay") || entry.boolean("Hidden") {
+                return false;
+            }
+            entry.shown_in(&desktops) && entry.try_exec()
+        })
+        .map(|entry| App::new(&entry))
+        .collect()
+}
+
+// Indexes the apps, then again whenever something in an applications dir changes. Runs on its
+// own thread for the lifetime of the shell.
+fn watch(apps: Arc<RwLock<Vec<App>>>) {
+    let mut inotify = match Inotify::init() {
+        Ok(inotify) => Some(inotify),
+        Err(error) => {
+            dbg!(error);
+            None
+        }
+    };
+    let mut buffer = [0u8; 4096];
+
+    loop {
+        // Parents of missing applications dirs, with the names of the dirs to wait for in them
+        let mut awaited: HashMap<WatchDescriptor, Vec<OsString>> = HashMap::new();
+        if let Some(inotify) = &inotify {
+            // Every time, since subdirectories may have been added. Adding a watch again just
+            // returns the existing one.
+            let (existing, missing): (Vec<PathBuf>, Vec<PathBuf>) =
+                application_dirs().into_iter().partition(|dir| dir.is_dir());
+            let dirs = existing.into_iter().flat_map(|dir| {
+                WalkDir::new(dir)
+                    .follow_links(true)
+                    .into_iter()
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_type().is_dir())
+            });
+            for dir in dirs {
+                if let Err(error) = inotify.watches().add(
+                    dir.path(),
+                    WatchMask::CLOSE_WRITE
+                        | WatchMask::CREATE
+                        | WatchMask::DELETE
+                        | WatchMask::MOVED_FROM
+                        | WatchMask::MOVED_TO,
+                ) {
+                    dbg!(error);
+                }
+            }
+            // A dir that doesn't exist yet, like ~/.local/share/applications before the first
+            // user install, is watched for through its closest existing parent. Once it's created
+            // this loop runs again and watches it.
+            for dir in missing {
+                let Some(parent) = dir.ancestors().skip(1).find(|parent| parent.is_dir()) else {
+                    continue;
+                };
+                let Some(name) = dir
+                    .strip_prefix(parent)
+                    .ok()
+                    .and_then(|rest| rest.components().next())
+                else {
+                    continue;
+                };
+                match inotify
+                    .watches()
+                    .add(parent, WatchMask::CREATE | WatchMask::MOVED_TO)
+                {
+                    Ok(watch) => awaited
+                        .entry(watch)
+                        .or_default()
+                        .push(name.as_os_str().to_os_string()),
+                    Err(error) => {
+                        dbg!(error);
+                    }
+                }
+            }
+        }
+
+        *apps.write().unwrap() = index();
+        // So the launcher's first icon lookups don't load the theme while it's open
+        icon_theme::preload();
+
+        let Some(inotify) = &mut inotify else {
+            return;
+        };
+        // Anything else created next to a missing dir, like ~/.local/share's other files,
+        // doesn't need another index
+        loop {
+            let mut events = match inotify.read_events_blocking(&mut buffer) {
+                Ok(events) => events,
+                Err(error) => {
+                    dbg!(error);
+                    return;
+                }
+            };
+            if events.any(|event| match awaited.get(&event.wd) {
+                Some(names) => event
+                    .name
+                    .is_some_and(|name| names.iter().any(|awaited| awaited == name)),
+                None => true,
+            }) {
+                break;
+            }
+        }
+        // Package managers change many files at once, so let them finish before indexing
+        std::thread::sleep(Duration::from_millis(500));
+        while inotify
+            .read_events(&mut buffer)
+            .is_ok_and(|mut events| events.next().is_some())
+        {}
+    }
+}
+
+// Installed applications from their desktop entries
+#[derive(Debug)]
+pub struct Apps {
+    apps: Arc<RwLock<Vec<App>>>,
+    pub history: History,
+}
+
+impl Apps {
+    // Starts indexing right away, so the apps are there by the time the launcher first opens
+    pub fn new() -> Self {
+        let apps = Arc::new(RwLock::new(Vec::new()));
+        let watched = apps.clone();
+        std::thread::spawn(move || watch(watched));
+        Self {
+            apps,
+            history: History::load(),
+        }
+    }
+}
+
+impl SearchProvider for Apps {
+    fn refresh(&mut self) {
+        self.history = History::load();
+    }
+
+    fn search(&self, query: &str) -> Vec<LauncherItem> {
+        let query = query.to_lowercase();
+        let apps = self.apps.read().unwrap();
+        apps.iter()
+            .flat_map(|app| {
+                let mut items = Vec::new();
+
+                if let Some(launch) = &app.launch {
+                    let (score, highlights) = app.score(&query);
+                    let score = self.history.rank(score, &history_id(launch), &query);
+                    items.push(LauncherItem {
+                        title: app.name.clone(),
+                        highlights,
+                        subtitle: app.comment.clone(),
+                        icon: app.icon.clone(),
+                        action: Action::Launch(launch.clone()),
+                        score,
+                    });
+                }
+
+                // A bit below the app itself
+                items.extend(app.actions.iter().map(|action| {
+                    let (score, highlights) = match fuzzy_match(&action.title, &query) {
+                        Some(found) => (found.score * 0.9, found.positions),
+                        None => (0.0, Vec::new()),
+                    };
+                    LauncherItem {
+                        title: action.title.clone(),
+                        highlights,
+                        subtitle: None,
+                        icon: action.icon.clone(),
+                        action: Action::Launch(action.launch.clone()),
+                        score: self
+                            .history
+                            .rank(score, &history_id(&action.launch), &query),
+                    }
+                }));
+
+                items
+            })
+            .collect()
+    }
+
+    fn activated(&mut self, item: &LauncherItem, query: &str) {
+        if let Action::Launch(launch) = &item.action {
+            self.history.record(&history_id(launch), query);
+        }
+    }
+
+    fn forget(&mut self, item: &LauncherItem) {
+        if let Action::Launch(launch) = &item.action {
+            self.history.forget(&history_id(launch));
+        }
+    }
+}
@@ -0,0 +1,595 @@
+use std::f64::consts;
+
+use super::provider::{Action, LauncherItem, SearchProvider};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    // One of + - * / % ^ ( ) ,
+    Symbol(char),
+}
+
+// As it was typed, for error messages
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", format(*value)),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+fn parse_radix(digits: &str, radix: u32) -> Result<f64, String> {
+    i64::from_str_radix(digits, radix)
+        .map(|value| value as f64)
+        .map_err(|_| format!("Invalid number 0{}{}", radix_prefix(radix), digits))
+}
+
+fn radix_prefix(radix: u32) -> char {
+    match radix {
+        16 => 'x',
+        8 => 'o',
+        _ => 'b',
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '0' && matches!(chars.get(i + 1), Some('x' | 'X' | 'b' | 'B' | 'o' | 'O')) {
+            let radix = match chars[i + 1].to_ascii_lowercase() {
+                'x' => 16,
+                'o' => 8,
+                _ => 2,
+            };
+            let start = i + 2;
+            i = start;
+            while i < chars.len() && chars[i].is_digit(radix) {
+                i += 1;
+            }
+            let digits: String = chars[start..i].iter().collect();
+            tokens.push(Token::Number(parse_radix(&digits, radix)?));
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // An exponent only counts when digits follow, so "2e" is still 2 times e
+            if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                let mut end = i + 1;
+                if matches!(chars.get(end), Some('+' | '-')) {
+                    end += 1;
+                }
+                if chars.get(end).is_some_and(|c| c.is_ascii_digit()) {
+                    i = end;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let number: String = chars[start..i].iter().collect();
+            tokens.push(Token::Number(
+                number
+                    .parse()
+                    .map_err(|_| format!("Invalid number {}", number))?,
+            ));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(
+                chars[start..i].iter().collect::<String>().to_lowercase(),
+            ));
+        } else if c == '*' && chars.get(i + 1) == Some(&'*') {
+            tokens.push(Token::Symbol('^'));
+            i += 2;
+        } else {
+            let symbol = match c {
+                '×' => '*',
+                '÷' => '/',
+                '−' => '-',
+                '+' | '-' | '*' | '/' | '%' | '^' | '(' | ')' | ',' => c,
+                _ => return Err(format!("Unexpected {}", c)),
+            };
+            tokens.push(Token::Symbol(symbol));
+            i += 1;
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn call(name: &str, args: &[f64]) -> Result<f64, String> {
+    let one = |f: fn(f64) -> f64| match args {
+        [x] => Ok(f(*x)),
+        _ => Err(format!("{} takes one argument", name)),
+    };
+    match name {
+        "sqrt" => one(f64::sqrt),
+        "cbrt" => one(f64::cbrt),
+        "abs" => one(f64::abs),
+        "exp" => one(f64::exp),
+        "ln" => one(f64::ln),
+        "log2" => one(f64::log2),
+        "log10" => one(f64::log10),
+        "log" => match args {
+            [x] => Ok(x.log10()),
+            [x, base] => Ok(x.log(*base)),
+            _ => Err("log takes one or two arguments".to_string()),
+        },
+        "sin" => one(f64::sin),
+        "cos" => one(f64::cos),
+        "tan" => one(f64::tan),
+        "asin" => one(f64::asin),
+        "acos" => one(f64::acos),
+        "atan" => one(f64::atan),
+        "sinh" => one(f64::sinh),
+        "cosh" => one(f64::cosh),
+        "tanh" => one(f64::tanh),
+        "floor" => one(f64::floor),
+        "ceil" => one(f64::ceil),
+        "round" => one(f64::round),
+        "trunc" => one(f64::trunc),
+        "pow" => match args {
+            [x, y] => Ok(x.powf(*y)),
+            _ => Err("pow takes two arguments".to_string()),
+        },
+        "min" if !args.is_empty() => Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+        "max" if !args.is_empty() => Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+        "min" | "max" => Err(format!("{} takes at least one argument", name)),
+        _ => Err(format!("Unknown function {}", name)),
+    }
+}
+
+// Recursive descent over the tokens, lowest precedence first:
+//   expr  = term (("+" | "-") term)*
+//   term  = unary (("*" | "/" | "%") unary | unary)*   the bare unary is implied, e.g. 2pi
+//   unary = ("-" | "+") unary | power
+//   power = atom ("^" unary)?                           right associative, -2^2 is -4
+//   atom  = number | name | name "(" args ")" | "(" expr ")"
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    ans: Option<f64>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expr(&mut self) -> Result<f64, String> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                value += self.term()?;
+            } else if self.eat('-') {
+                value -= self.term()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<f64, String> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value *= self.unary()?;
+            } else if self.eat('/') {
+                let divisor = self.unary()?;
+                if divisor == 0.0 {
+                    return Err("Division by zero".to_string());
+                }
+                value /= divisor;
+            } else if self.eat('%') {
+                let divisor = self.unary()?;
+                if divisor == 0.0 {
+                    return Err("Division by zero".to_string());
+                }
+                value %= divisor;
+            } else if matches!(self.peek(), Some(Token::Ident(_) | Token::Symbol('('))) {
+                value *= self.unary()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<f64, String> {
+        if self.eat('-') {
+            return Ok(-self.unary()?);
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<f64, String> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            return Ok(base.powf(self.unary()?));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<f64, String> {
+        match self.next().cloned() {
+            Some(Token::Number(value)) => Ok(value),
+            Some(Token::Symbol('(')) => {
+                let value = self.expr()?;
+                if !self.eat(')') {
+                    return Err("Missing )".to_string());
+                }
+                Ok(value)
+            }
+            Some(Token::Ident(name)) => {
+                if self.eat('(') {
+                    let mut args = Vec::new();
+                    if !self.eat(')') {
+                        loop {
+                            args.push(self.expr()?);
+                            if self.eat(')') {
+                                break;
+                            }
+                            if !self.eat(',') {
+                                return Err("Missing )".to_string());
+                            }
+                        }
+                    }
+                    return call(&name, &args);
+                }
+                match name.as_str() {
+                    "pi" | "π" => Ok(consts::PI),
+                    "tau" | "τ" => Ok(consts::TAU),
+                    "e" => Ok(consts::E),
+                    "ans" => self.ans.ok_or_else(|| "No previous result".to_string()),
+                    _ => Err(format!("Unknown name {}", name)),
+                }
+            }
+            Some(Token::Symbol(symbol)) => Err(format!("Unexpected {}", symbol)),
+            None => Err("Unexpected end of input".to_string()),
+        }
+    }
+}
+
+// `ans` is the value of the previous result, if there is one
+pub fn evaluate(input: &str, ans: Option<f64>) -> Result<f64, String> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+        ans,
+    };
+    let value = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("Unexpected {}", token));
+    }
+    if !value.is_finite() {
+        return Err("Result is not a finite number".to_string());
+    }
+    Ok(value)
+}
+
+// Whole numbers without a fraction and the rest with up to 10 decimals
+pub fn format(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        return format!("{}", value as i64);
+    }
+    if value != 0.0 && value.abs() < 1e-6 {
+        return format!("{:e}", value);
+    }
+    let formatted = format!("{:.10}", value);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+#[derive(Debug, Default)]
+pub struct Calculator {
+    // The last result copied from the launcher, available as `ans`
+    pub ans: Option<f64>,
+}
+
+impl SearchProvider for Calculator {
+    fn search(&self, query: &str) -> Vec<LauncherItem> {
+        let query = query.trim();
+        // Keeps names like "e" or "pi" from turning app searches into calculations
+        if !query.chars().any(|c| c.is_ascii_digit() || c == '(') {
+            return Vec::new();
+        }
+
+        match evaluate(query, self.ans) {
+            Ok(value) => {
+                let result = format(value);
+                // Typing a plain number isn't a calculation
+                if result == query {
+                    return Vec::new();
+                }
+                vec![LauncherItem {
+                    title: result.clone(),
+                    highlights: Vec::new(),
+                    subtitle: Some(format!("{} (Enter to copy)", query)),
+                    icon: Some("accessories-calculator".to_string()),
+                    action: Action::Copy(result),
+                    // Above everything else, an expression is never an app name
+                    score: 1.0,
+                }]
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn activated(&mut self, item: &LauncherItem, _query: &str) {
+        // Copy actions only come from here, so this is one of our results
+        if let Action::Copy(result) = &item.action {
+            if let Ok(value) = result.parse() {
+                self.ans = Some(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(input: &str) -> f64 {
+        evaluate(input, None).unwrap()
+    }
+
+    fn assert_close(input: &str, expected: f64) {
+        let value = eval(input);
+        assert!(
+            (value - expected).abs() < 1e-9,
+            "{} = {}, expected {}",
+            input,
+            value,
+            expected
+        );
+    }
+
+    #[test]
+    fn respects_precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7.0);
+        assert_eq!(eval("(1 + 2) * 3"), 9.0);
+        assert_eq!(eval("2 * 3 ^ 2"), 18.0);
+        assert_eq!(eval("7 % 4 + 1"), 4.0);
+    }
+
+    #[test]
+    fn powers_are_right_associative() {
+        assert_eq!(eval("2^3^2"), 512.0);
+        assert_eq!(eval("2 ** 3 ** 2"), 512.0);
+        assert_eq!(eval("(2^3)^2"), 64.0);
+    }
+
+    #[test]
+    fn negation_binds_looser_than_powers() {
+        assert_eq!(eval("-2^2"), -4.0);
+        assert_eq!(eval("(-2)^2"), 4.0);
+        assert_eq!(eval("2^-1"), 0.5);
+        assert_eq!(eval("--3"), 3.0);
+        assert_eq!(eval("+3 - -3"), 6.0);
+    }
+
+    #[test]
+    fn other_operators_are_left_associative() {
+        assert_eq!(eval("10 / 4 * 2"), 5.0);
+        assert_eq!(eval("10 - 4 - 3"), 3.0);
+        assert_eq!(eval("100 / 10 / 5"), 2.0);
+        assert_eq!(eval("17 % 10 % 4"), 3.0);
+    }
+
+    #[test]
+    fn multiplies_implicitly() {
+        assert_eq!(eval("2pi"), 2.0 * consts::PI);
+        assert_eq!(eval("2(3)"), 6.0);
+        assert_eq!(eval("(1 + 1)(2 + 2)"), 8.0);
+        assert_eq!(eval("3 sqrt(4)"), 6.0);
+        // Implicit multiplication binds like explicit, so this is (1/2)*pi
+        assert_eq!(eval("1/2pi"), consts::PI / 2.0);
+    }
+
+    #[test]
+    fn tells_exponents_from_e() {
+        assert_eq!(eval("2e3"), 2000.0);
+        assert_eq!(eval("1.5e-3"), 0.0015);
+        assert_eq!(eval("2e"), 2.0 * consts::E);
+        assert_eq!(eval("2e+1"), 20.0);
+        assert_eq!(eval("2e + 1"), 2.0 * consts::E + 1.0);
+    }
+
+    #[test]
+    fn accepts_other_operator_symbols() {
+        assert_eq!(eval("3 × 4 ÷ 2 − 1"), 5.0);
+        assert_eq!(eval("π"), consts::PI);
+        assert_eq!(eval("tau"), consts::TAU);
+        assert_eq!(eval("PI"), consts::PI);
+    }
+
+    #[test]
+    fn parses_radix_literals() {
+        assert_eq!(eval("0xff"), 255.0);
+        assert_eq!(eval("0XFF"), 255.0);
+        assert_eq!(eval("0b101"), 5.0);
+        assert_eq!(eval("0o17"), 15.0);
+        assert_eq!(eval("0x10 + 0b10"), 18.0);
+        assert_eq!(eval("0x7fffffffffffffff"), i64::MAX as f64);
+    }
+
+    #[test]
+    fn rejects_malformed_radix_literals() {
+        assert_eq!(evaluate("0x", None), Err("Invalid number 0x".to_string()));
+        assert_eq!(evaluate("0b", None), Err("Invalid number 0b".to_string()));
+        assert_eq!(evaluate("0b2", None), Err("Invalid number 0b".to_string()));
+        assert_eq!(evaluate("0o8", None), Err("Invalid number 0o".to_string()));
+        // One more than i64::MAX
+        assert_eq!(
+            evaluate("0x8000000000000000", None),
+            Err("Invalid number 0x8000000000000000".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_decimals() {
+        assert_eq!(
+            evaluate("1.2.3", None),
+            Err("Invalid number 1.2.3".to_string())
+        );
+        assert_eq!(evaluate(".", None), Err("Invalid number .".to_string()));
+    }
+
+    #[test]
+    fn calls_functions() {
+        assert_eq!(eval("sqrt(16)"), 4.0);
+        assert_eq!(eval("abs(-3)"), 3.0);
+        assert_eq!(eval("floor(2.7) + ceil(2.1)"), 5.0);
+        assert_eq!(eval("min(3, 1, 2)"), 1.0);
+        assert_eq!(eval("max(3, 1, 2)"), 3.0);
+        assert_eq!(eval("pow(2, 10)"), 1024.0);
+        assert_eq!(eval("SQRT(9)"), 3.0);
+        assert_close("log(1000)", 3.0);
+        assert_close("log(8, 2)", 3.0);
+        assert_close("ln(e)", 1.0);
+        assert_close("sin(pi / 2)", 1.0);
+        assert_close("cos(0)", 1.0);
+    }
+
+    #[test]
+    fn checks_arity() {
+        assert_eq!(
+            evaluate("sqrt()", None),
+            Err("sqrt takes one argument".to_string())
+        );
+        assert_eq!(
+            evaluate("sqrt(1, 2)", None),
+            Err("sqrt takes one argument".to_string())
+        );
+        assert_eq!(
+            evaluate("pow(2)", None),
+            Err("pow takes two arguments".to_string())
+        );
+        assert_eq!(
+            evaluate("log(1, 2, 3)", None),
+            Err("log takes one or two arguments".to_string())
+        );
+        assert_eq!(
+            evaluate("max()", None),
+            Err("max takes at least one argument".to_string())
+        );
+        assert_eq!(
+            evaluate("foo(1)", None),
+            Err("Unknown function foo".to_string())
+        );
+        assert_eq!(evaluate("foo", None), Err("Unknown name foo".to_string()));
+    }
+
+    #[test]
+    fn uses_the_previous_result() {
+        assert_eq!(evaluate("ans * 2", Some(21.0)), Ok(42.0));
+        assert_eq!(evaluate("2ans", Some(1.5)), Ok(3.0));
+        assert_eq!(
+            evaluate("ans + 1", None),
+            Err("No previous result".to_string())
+        );
+    }
+
+    #[test]
+    fn remembers_copied_results() {
+        let mut calculator = Calculator::default();
+        assert!(calculator.search("ans + 1").is_empty());
+
+        let items = calculator.search("6 * 7");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].title, "42");
+        calculator.activated(&items[0], "6 * 7");
+        assert_eq!(calculator.ans, Some(42.0));
+        assert_eq!(calculator.search("ans + 1")[0].title, "43");
+    }
+
+    #[test]
+    fn ignores_queries_that_arent_calculations() {
+        let calculator = Calculator::default();
+        assert!(calculator.search("firefox").is_empty());
+        assert!(calculator.search("pi").is_empty());
+        assert!(calculator.search("42").is_empty());
+        assert!(calculator.search("2 +").is_empty());
+    }
+
+    #[test]
+    fn rejects_division_by_zero() {
+        let error = Err("Division by zero".to_string());
+        assert_eq!(evaluate("1 / 0", None), error);
+        assert_eq!(evaluate("1 % 0", None), error);
+        assert_eq!(evaluate("1 / (2 - 2)", None), error);
+    }
+
+    #[test]
+    fn rejects_non_finite_results() {
+        let error = Err("Result is not a finite number".to_string());
+        assert_eq!(evaluate("sqrt(-1)", None), error);
+        assert_eq!(evaluate("ln(0)", None), error);
+        assert_eq!(evaluate("10^400", None), error);
+        assert_eq!(evaluate("1e308 * 10", None), error);
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        assert_eq!(
+            evaluate("1 +", None),
+            Err("Unexpected end of input".to_string())
+        );
+        assert_eq!(
+            evaluate("", None),
+            Err("Unexpected end of input".to_string())
+        );
+        assert_eq!(evaluate("(1 + 2", None), Err("Missing )".to_string()));
+        assert_eq!(evaluate("max(1 2)", None), Err("Missing )".to_string()));
+        assert_eq!(evaluate("1 + )", None), Err("Unexpected )".to_string()));
+        assert_eq!(evaluate("2 $ 3", None), Err("Unexpected $".to_string()));
+        assert_eq!(evaluate("1 2", None), Err("Unexpected 2".to_string()));
+        assert_eq!(evaluate("1 2.5", None), Err("Unexpected 2.5".to_string()));
+        assert_eq!(evaluate("(1))", None), Err("Unexpected )".to_string()));
+    }
+
+    #[test]
+    fn formats_results() {
+        assert_eq!(format(4.0), "4");
+        assert_eq!(format(-12.0), "-12");
+        assert_eq!(format(0.0), "0");
+        assert_eq!(format(-2.5), "-2.5");
+        assert_eq!(format(0.1 + 0.2), "0.3");
+        assert_eq!(format(1.0 / 3.0), "0.3333333333");
+        assert_eq!(format(2.0 / 3.0), "0.6666666667");
+        assert_eq!(format(1e-7), "1e-7");
+        assert_eq!(format(-2.5e-9), "-2.5e-9");
+        assert_eq!(format(123456789012345.0), "123456789012345");
+        assert_eq!(format(1e20), "100000000000000000000");
+    }
+}
@@ -0,0 +1,264 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use inotify::{Inotify, WatchMask};
+
+use super::launch::Launch;
+use super::provider::{Action, LauncherItem, SearchProvider};
+use crate::config;
+
+// How many commands are remembered
+const HISTORY_LENGTH: usize = 100;
+
+fn history_path() -> PathBuf {
+    config::state_dir().join("command-history")
+}
+
+// Quotes text for a POSIX shell
+pub fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+fn user_shell() -> String {
+    std::env::var("SHELL").unwrap_or("/bin/sh".to_string())
+}
+
+// Arguments running `command` through the user's shell
+pub fn shell_command(command: &str) -> Vec<String> {
+    vec![user_shell(), "-c".to_string(), command.to_string()]
+}
+
+// Arguments running `argv` in a terminal, which may be configured with options of its own
+pub fn terminal_command(argv: &[String]) -> Vec<String> {
+    let config = config::get();
+    let terminal = config
+        .launcher
+        .terminal
+        .clone()
+        .or(std::env::var("TERMINAL").ok())
+        .unwrap_or("xterm".to_string());
+    terminal
+        .split_whitespace()
+        .map(str::to_string)
+        .chain(["-e".to_string()])
+        .chain(argv.iter().cloned())
+        .collect()
+}
+
+// A typed command. In a terminal, it stays open on the user's shell after the command.
+pub fn launch(command: &str, in_terminal: bool) -> Launch {
+    let argv = if in_terminal {
+        shell_command(&format!("{}; exec {}", command, quote(&user_shell())))
+    } else {
+        shell_command(command)
+    };
+    Launch {
+        // The program's name, as far as a shell command has one
+        id: command
+            .split_whitespace()
+            .next()
+            .and_then(|program| program.rsplit('/').next())
+            .unwrap_or("command")
+            .to_string(),
+        name: command.to_string(),
+        argv,
+        terminal: in_terminal,
+        ..Default::default()
+    }
+}
+
+fn path_dirs() -> Vec<PathBuf> {
+    std::env::var("PATH")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+// Names of everything executable in $PATH, shortest first so completions stay useful
+fn executables() -> Vec<String> {
+    let mut executables: Vec<String> = path_dirs()
+        .into_iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        // Following symlinks, plenty of programs are links into /opt or similar
+        .filter(|entry| {
+            std::fs::metadata(entry.path()).is_ok_and(|metadata| {
+                metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+            })
+        })
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    executables.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+    executables.dedup();
+    executables
+}
+
+// Lists the executables, then again whenever something in a $PATH directory changes. Runs on
+// its own thread for the lifetime of the shell.
+fn watch(list: Arc<RwLock<Vec<String>>>) {
+    let mut inotify = match Inotify::init() {
+        Ok(inotify) => Some(inotify),
+        Err(error) => {
+            dbg!(error);
+            None
+        }
+    };
+    let mut buffer = [0u8; 4096];
+
+    loop {
+        if let Some(inotify) = &inotify {
+            // Making a file executable only changes its attributes
+            for dir in path_dirs().into_iter().filter(|dir| dir.is_dir()) {
+                if let Err(error) = inotify.watches().add(
+                    &dir,
+                    WatchMask::CREATE
+                        | WatchMask::DELETE
+                        | WatchMask::MOVED_FROM
+                        | WatchMask::MOVED_TO
+                        | WatchMask::ATTRIB,
+                ) {
+                    dbg!(error);
+                }
+            }
+        }
+
+        *list.write().unwrap() = executables();
+
+        let Some(inotify) = &mut inotify else {
+            return;
+        };
+        if let Err(error) = inotify.read_events_blocking(&mut buffer) {
+            dbg!(error);
+            return;
+        }
+        // Package managers change many files at once, so let them finish before listing again
+        std::thread::sleep(Duration::from_millis(500));
+        while inotify
+            .read_events(&mut buffer)
+            .is_ok_and(|mut events| events.next().is_some())
+        {}
+    }
+}
+
+// Shell commands typed after the configured prefix
+#[derive(Debug)]
+pub struct Commands {
+    executables: Arc<RwLock<Vec<String>>>,
+    // Most recent first
+    pub history: Vec<String>,
+}
+
+impl Commands {
+    // Lists the executables in the background, so opening the launcher doesn't wait on $PATH
+    pub fn new() -> Self {
+        let executables = Arc::new(RwLock::new(Vec::new()));
+        let watched = executables.clone();
+        std::thread::spawn(move || watch(watched));
+        Self {
+            executables,
+            history: Vec::new(),
+        }
+    }
+
+    fn save_history(&self) {
+        let path = history_path();
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Err(error) = std::fs::write(path, self.history.join("\n")) {
+            dbg!(error);
+        }
+    }
+}
+
+impl SearchProvider for Commands {
+    fn prefix(&self) -> Option<String> {
+        Some(config::get().launcher.command_prefix.clone())
+    }
+
+    fn refresh(&mut self) {
+        self.history = std::fs::read_to_string(history_path())
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+
+    fn search(&self, query: &str) -> Vec<LauncherItem> {
+        let query = query.trim_start();
+        let mut items = Vec::new();
+
+        if !query.trim().is_empty() {
+            items.push(LauncherItem {
+                title: query.to_string(),
+                highlights: Vec::new(),
+                subtitle: Some("Run command, Shift+Enter runs it in a terminal".to_string()),
+                icon: Some("utilities-terminal".to_string()),
+                action: Action::Command(query.to_string()),
+                score: 1.0,
+            });
+        }
+
+        items.extend(
+            self.history
+                .iter()
+                .filter(|command| command.starts_with(query) && command.as_str() != query)
+                .map(|command| LauncherItem {
+                    title: command.clone(),
+                    highlights: (0..query.chars().count()).collect(),
+                    subtitle: Some("Recent command".to_string()),
+                    icon: Some("document-open-recent".to_string()),
+                    action: Action::Command(command.clone()),
+                    score: 0.9,
+                }),
+        );
+
+        // Only the program name is completed, not its arguments
+        if !query.is_empty() && !query.contains(char::is_whitespace) {
+            items.extend(
+                self.executables
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .filter(|executable| {
+                        executable.starts_with(query) && executable.as_str() != query
+                    })
+                    .filter(|executable| !self.history.contains(executable))
+                    .map(|executable| LauncherItem {
+                        title: executable.clone(),
+                        highlights: (0..query.chars().count()).collect(),
+                        subtitle: Some("Program".to_string()),
+                        icon: Some("application-x-executable".to_string()),
+                        action: Action::Command(executable.clone()),
+                        score: 0.5,
+                    }),
+            );
+        }
+
+        items
+    }
+
+    fn activated(&mut self, item: &LauncherItem, _query: &str) {
+        if let Action::Command(command) = &item.action {
+            self.history.retain(|recent| recent != command);
+            self.history.insert(0, command.clone());
+            self.history.truncate(HISTORY_LENGTH);
+            self.save_history();
+        }
+    }
+
+    fn forget(&mut self, item: &LauncherItem) {
+        if let Action::Command(command) = &item.action {
+            if self.history.contains(command) {
+                self.history.retain(|recent| recent != command);
+                self.save_history();
+            }
+        }
+    }
+}
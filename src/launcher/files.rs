@@ -0,0 +1,254 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use walkdir::WalkDir;
+
+use super::glob::{self, Exclude};
+use super::mime;
+use super::provider::{Action, LauncherItem, SearchProvider};
+use crate::config;
+
+// How many new files are collected before searches can see them during the first build
+const BATCH_SIZE: usize = 1000;
+
+fn home() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap())
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) => home().join(rest.trim_start_matches('/')),
+        None => PathBuf::from(path),
+    }
+}
+
+// The inverse of expand_home, for showing paths
+fn shorten_home(path: &Path) -> String {
+    match path.strip_prefix(home()) {
+        Ok(rest) => format!("~/{}", rest.display()),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct IndexedFile {
+    path: PathBuf,
+    // Lowercase, which is what's searched
+    name: String,
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    files: Vec<IndexedFile>,
+    building: bool,
+    built_at: Option<Instant>,
+}
+
+// Walks the roots on another thread. The first build adds files as they're found so results
+// show up right away, later ones keep searching the old index until they're done.
+fn build(index: Arc<RwLock<Index>>) {
+    let config = config::get();
+    let roots: Vec<PathBuf> = config
+        .launcher
+        .file_roots
+        .iter()
+        .map(|root| expand_home(root))
+        .collect();
+    let excludes: Vec<Exclude> = config
+        .launcher
+        .file_excludes
+        .iter()
+        .filter_map(|line| Exclude::parse(line))
+        .collect();
+    let hidden = config.launcher.file_hidden;
+    let limit = config.launcher.file_limit;
+
+    let first = index.read().unwrap().files.is_empty();
+    let mut files = Vec::new();
+    let mut count = 0;
+
+    'roots: for root in roots {
+        let walker = WalkDir::new(&root)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|entry| {
+                if entry.depth() == 0 {
+                    return true;
+                }
+                let name = entry.file_name().to_string_lossy();
+                if !hidden && name.starts_with('.') {
+                    return false;
+                }
+                let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+                let relative = relative.to_string_lossy();
+                let is_dir = entry.file_type().is_dir();
+                !glob::excluded(&excludes, &relative, is_dir)
+            });
+
+        for entry in walker.filter_map(|entry| entry.ok()) {
+            if entry.depth() == 0 {
+                continue;
+            }
+            files.push(IndexedFile {
+                name: entry.file_name().to_string_lossy().to_lowercase(),
+                path: entry.into_path(),
+            });
+            count += 1;
+
+            if first && files.len() >= BATCH_SIZE {
+                index.write().unwrap().files.append(&mut files);
+            }
+            if count >= limit {
+                break 'roots;
+            }
+        }
+    }
+
+    let mut index = index.write().unwrap();
+    if first {
+        index.files.append(&mut files);
+    } else {
+        index.files = files;
+    }
+    index.building = false;
+    index.built_at = Some(Instant::now());
+}
+
+// The characters of the name that contain the lowercase query. Lowercasing can turn one
+// character into several (İ becomes i and a combining dot), so the match is mapped back to the
+// characters of the name.
+fn highlights(name: &str, query: &str) -> Vec<usize> {
+    let lowercase: Vec<(usize, char)> = name
+        .chars()
+        .enumerate()
+        .flat_map(|(index, c)| c.to_lowercase().map(move |lower| (index, lower)))
+        .collect();
+    let text: String = lowercase.iter().map(|(_, c)| c).collect();
+    match text.find(query) {
+        Some(start) => {
+            let start = text[..start].chars().count();
+            let mut highlights: Vec<usize> = lowercase[start..start + query.chars().count()]
+                .iter()
+                .map(|(index, _)| *index)
+                .collect();
+            highlights.dedup();
+            highlights
+        }
+        None => Vec::new(),
+    }
+}
+
+// File and directory names below the configured roots
+#[derive(Debug, Default)]
+pub struct Files {
+    index: Arc<RwLock<Index>>,
+}
+
+impl SearchProvider for Files {
+    fn prefix(&self) -> Option<String> {
+        Some(config::get().launcher.file_prefix.clone())
+    }
+
+    fn refresh(&mut self) {
+        let reindex_after = Duration::from_secs(config::get().launcher.file_reindex_interval);
+        {
+            let mut index = self.index.write().unwrap();
+            let stale = index
+                .built_at
+                .is_none_or(|built_at| built_at.elapsed() >= reindex_after);
+            if index.building || !stale {
+                return;
+            }
+            index.building = true;
+        }
+
+        let index = self.index.clone();
+        std::thread::spawn(move || build(index));
+    }
+
+    fn search(&self, query: &str) -> Vec<LauncherItem> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let index = self.index.read().unwrap();
+        let mut matches: Vec<(&IndexedFile, f32)> = index
+            .files
+            .iter()
+            .filter_map(|file| {
+                let score = if file.name == query {
+                    0.9
+                } else if file.name.starts_with(&query) {
+                    0.7
+                } else if file.name.contains(&query) {
+                    0.5
+                } else {
+                    return None;
+                };
+                Some((file, score))
+            })
+            .collect();
+        // Shallower paths first among equally good names
+        matches.sort_by(|a, b| {
+            b.1.total_cmp(&a.1).then(
+                a.0.path
+                    .components()
+                    .count()
+                    .cmp(&b.0.path.components().count()),
+            )
+        });
+        matches.truncate(20);
+
+        matches
+            .into_iter()
+            .map(|(file, score)| {
+                let title = file
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                LauncherItem {
+                    highlights: highlights(&title, &query),
+                    title,
+                    subtitle: Some(shorten_home(&file.path)),
+                    icon: Some(mime::icon(&mime::mime_type(&file.path))),
+                    action: Action::Open(file.path.clone()),
+                    score,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlights_the_matched_characters() {
+        assert_eq!(highlights("Report.pdf", "port"), [2, 3, 4, 5]);
+        assert_eq!(highlights("README.md", "readme"), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(highlights("notes.txt", "pdf"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn highlights_the_first_occurrence() {
+        assert_eq!(highlights("abab", "ab"), [0, 1]);
+    }
+
+    #[test]
+    fn maps_multibyte_characters_back() {
+        assert_eq!(highlights("Café Menü.txt", "menü"), [5, 6, 7, 8]);
+        assert_eq!(highlights("日本語.txt", "語"), [2]);
+    }
+
+    #[test]
+    fn maps_characters_that_lowercase_to_several() {
+        // İ lowercases to i and a combining dot, both belong to the first character
+        assert_eq!(highlights("İstanbul", "i\u{307}st"), [0, 1, 2]);
+        assert_eq!(highlights("İstanbul", "stan"), [1, 2, 3, 4]);
+        assert_eq!(highlights("xİy", "i\u{307}y"), [1, 2]);
+    }
+}
@@ -0,0 +1,261 @@
+// Shell-style glob matching as used by .gitignore files and shared-mime-info:
+// `*` matches within one path component, `**` across components, `?` one character and
+// `[a-z]` or `[!a-z]` one character of (or not of) a class
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches_from(&pattern, &text)
+}
+
+fn matches_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            // Zero or more whole directories
+            matches_from(rest, text)
+                || (0..text.len())
+                    .filter(|&i| text[i] == '/')
+                    .any(|i| matches_from(rest, &text[i + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| matches_from(rest, &text[i..])),
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| matches_from(rest, &text[i..])),
+        ['?', rest @ ..] => !text.is_empty() && text[0] != '/' && matches_from(rest, &text[1..]),
+        ['[', rest @ ..] => match text.first() {
+            Some(&c) if c != '/' => match class(pattern, c) {
+                Some((matched, rest)) => matched && matches_from(rest, &text[1..]),
+                // Without a closing bracket it's just a bracket
+                None => c == '[' && matches_from(rest, &text[1..]),
+            },
+            _ => false,
+        },
+        [c, rest @ ..] => text.first() == Some(c) && matches_from(rest, &text[1..]),
+    }
+}
+
+// Whether the bracket expression the pattern starts with matches `c`, with the rest of the
+// pattern after it. A `]` right after the opening bracket (or its `!`) is part of the class.
+fn class(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let negated = matches!(pattern.get(1), Some('!' | '^'));
+    let start = if negated { 2 } else { 1 };
+    let mut i = start;
+    let mut matched = false;
+    loop {
+        let first = *pattern.get(i)?;
+        if first == ']' && i > start {
+            break;
+        }
+        match pattern.get(i + 1..i + 3) {
+            Some(['-', last]) if *last != ']' => {
+                matched |= (first..=*last).contains(&c);
+                i += 3;
+            }
+            _ => {
+                matched |= first == c;
+                i += 1;
+            }
+        }
+    }
+    Some((matched != negated, &pattern[i + 1..]))
+}
+
+// One line of a .gitignore-style exclude list
+#[derive(Debug, Clone)]
+pub struct Exclude {
+    pattern: String,
+    // Patterns with a slash before their end match the path from the root,
+    // others match the name of any file or directory
+    anchored: bool,
+    // A trailing slash only matches directories
+    directory_only: bool,
+    // A leading ! includes what earlier patterns excluded
+    negated: bool,
+}
+
+impl Exclude {
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        // \! and \# are patterns starting with a literal ! or #
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let directory_only = line.ends_with('/');
+        let line = line.trim_end_matches('/');
+        if line.is_empty() {
+            return None;
+        }
+        Some(Self {
+            anchored: line.contains('/'),
+            pattern: line.trim_start_matches('/').to_string(),
+            directory_only,
+            negated,
+        })
+    }
+
+    // `relative` is the path below the root being indexed
+    pub fn matches(&self, relative: &str, is_dir: bool) -> bool {
+        if self.directory_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            matches(&self.pattern, relative)
+        } else {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            matches(&self.pattern, name)
+        }
+    }
+}
+
+// Whether a path below the root is excluded. Like in .gitignore the last pattern that matches
+// decides, so a ! pattern brings back what an earlier one excluded. Nothing below an excluded
+// directory is looked at, so it can't be brought back.
+pub fn excluded(excludes: &[Exclude], relative: &str, is_dir: bool) -> bool {
+    excludes
+        .iter()
+        .rev()
+        .find(|exclude| exclude.matches(relative, is_dir))
+        .is_some_and(|exclude| !exclude.negated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excludes(lines: &[&str]) -> Vec<Exclude> {
+        lines
+            .iter()
+            .filter_map(|line| Exclude::parse(line))
+            .collect()
+    }
+
+    #[test]
+    fn matches_literals() {
+        assert!(matches("notes.txt", "notes.txt"));
+        assert!(!matches("notes.txt", "notes.txt~"));
+        assert!(!matches("notes.txt", "my-notes.txt"));
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+    }
+
+    #[test]
+    fn star_stays_in_one_component() {
+        assert!(matches("*.png", "image.png"));
+        assert!(matches("*.png", ".png"));
+        assert!(matches("*", "anything"));
+        assert!(matches("a*b*c", "a-b-c"));
+        assert!(!matches("*.png", "image.jpg"));
+        assert!(!matches("*.png", "dir/image.png"));
+        assert!(matches("dir/*.png", "dir/image.png"));
+        assert!(!matches("dir/*.png", "dir/sub/image.png"));
+    }
+
+    #[test]
+    fn double_star_crosses_components() {
+        assert!(matches("**/target", "target"));
+        assert!(matches("**/target", "a/b/target"));
+        assert!(!matches("**/target", "a/b/target2"));
+        assert!(matches("build/**", "build/a/b"));
+        assert!(matches("a/**/b", "a/b"));
+        assert!(matches("a/**/b", "a/x/y/b"));
+        assert!(!matches("a/**/b", "a/xb"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(matches("file?.txt", "file1.txt"));
+        assert!(matches("file?.txt", "fileé.txt"));
+        assert!(!matches("file?.txt", "file.txt"));
+        assert!(!matches("file?.txt", "file12.txt"));
+        assert!(!matches("a?b", "a/b"));
+    }
+
+    #[test]
+    fn matches_character_classes() {
+        assert!(matches("*.[1-9]", "ls.1"));
+        assert!(!matches("*.[1-9]", "ls.0"));
+        assert!(matches("[abc].txt", "b.txt"));
+        assert!(!matches("[abc].txt", "d.txt"));
+        assert!(matches("[!abc].txt", "d.txt"));
+        assert!(matches("[^abc].txt", "d.txt"));
+        assert!(!matches("[!abc].txt", "a.txt"));
+        assert!(matches("[a-cx-z]", "y"));
+        // ] first and - last are literal
+        assert!(matches("[]a]", "]"));
+        assert!(matches("[a-]", "-"));
+        assert!(!matches("a[/]b", "a/b"));
+        // An unclosed bracket is a literal one
+        assert!(matches("[abc", "[abc"));
+        assert!(!matches("[abc", "a"));
+    }
+
+    #[test]
+    fn parses_exclude_lines() {
+        assert!(Exclude::parse("").is_none());
+        assert!(Exclude::parse("   ").is_none());
+        assert!(Exclude::parse("# a comment").is_none());
+        assert!(Exclude::parse("!").is_none());
+        assert!(Exclude::parse("/").is_none());
+
+        let exclude = Exclude::parse("  target/  ").unwrap();
+        assert_eq!(exclude.pattern, "target");
+        assert!(exclude.directory_only);
+        assert!(!exclude.anchored);
+        assert!(!exclude.negated);
+
+        let exclude = Exclude::parse("/build").unwrap();
+        assert_eq!(exclude.pattern, "build");
+        assert!(exclude.anchored);
+
+        let exclude = Exclude::parse("!keep/me.txt").unwrap();
+        assert_eq!(exclude.pattern, "keep/me.txt");
+        assert!(exclude.anchored);
+        assert!(exclude.negated);
+
+        assert_eq!(
+            Exclude::parse("\\!important").unwrap().pattern,
+            "!important"
+        );
+        assert_eq!(Exclude::parse("\\#notes").unwrap().pattern, "#notes");
+    }
+
+    #[test]
+    fn anchors_patterns_with_slashes() {
+        let exclude = Exclude::parse("docs/*.pdf").unwrap();
+        assert!(exclude.matches("docs/a.pdf", false));
+        assert!(!exclude.matches("src/docs/a.pdf", false));
+
+        let exclude = Exclude::parse("/cache").unwrap();
+        assert!(exclude.matches("cache", true));
+        assert!(!exclude.matches("src/cache", true));
+
+        // Without a slash the name is matched at any depth
+        let exclude = Exclude::parse("*.log").unwrap();
+        assert!(exclude.matches("a.log", false));
+        assert!(exclude.matches("deep/down/a.log", false));
+    }
+
+    #[test]
+    fn trailing_slash_only_matches_directories() {
+        let exclude = Exclude::parse("node_modules/").unwrap();
+        assert!(exclude.matches("web/node_modules", true));
+        assert!(!exclude.matches("web/node_modules", false));
+    }
+
+    #[test]
+    fn last_matching_pattern_decides() {
+        let list = excludes(&["*.log", "!important.log"]);
+        assert!(excluded(&list, "debug.log", false));
+        assert!(!excluded(&list, "logs/important.log", false));
+        assert!(!excluded(&list, "notes.txt", false));
+
+        // An exclude after the negation wins again
+        let list = excludes(&["*.log", "!important.log", "/important.log"]);
+        assert!(excluded(&list, "important.log", false));
+        assert!(!excluded(&list, "sub/important.log", false));
+    }
+}
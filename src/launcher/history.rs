@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+// Older launches are forgotten, and only this many are kept
+const MAX_AGE_DAYS: u64 = 90;
+const MAX_LAUNCHES: usize = 1000;
+
+fn history_path() -> PathBuf {
+    config::state_dir().join("launch-history.json")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Launch {
+    // The desktop file ID without .desktop, with #<action> for desktop actions
+    id: String,
+    // What was typed to find it, lowercase
+    query: String,
+    // Seconds since the epoch
+    time: u64,
+}
+
+// Past launches, for ranking what's launched often and lately above the rest
+#[derive(Debug, Default)]
+pub struct History {
+    launches: Vec<Launch>,
+}
+
+impl History {
+    pub fn load() -> Self {
+        let launches = match std::fs::read_to_string(history_path()) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+                dbg!(error);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self { launches }
+    }
+
+    fn save(&self) {
+        let path = history_path();
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let result = serde_json::to_string(&self.launches)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| std::fs::write(path, contents).map_err(|error| error.to_string()));
+        if let Err(error) = result {
+            dbg!(error);
+        }
+    }
+
+    pub fn record(&mut self, id: &str, query: &str) {
+        let config = config::get();
+        // Excluding an app excludes its actions too
+        let excluded = config
+            .launcher
+            .history_exclude
+            .iter()
+            .any(|excluded| id == excluded || id.starts_with(&format!("{}#", excluded)));
+        if !config.launcher.history || excluded {
+            return;
+        }
+
+        let now = now();
+        self.launches.push(Launch {
+            id: id.to_string(),
+            query: query.trim().to_lowercase(),
+            time: now,
+        });
+        self.launches
+            .retain(|launch| now.saturating_sub(launch.time) < MAX_AGE_DAYS * 24 * 60 * 60);
+        let excess = self.launches.len().saturating_sub(MAX_LAUNCHES);
+        self.launches.drain(..excess);
+        self.save();
+    }
+
+    pub fn forget(&mut self, id: &str) {
+        let count = self.launches.len();
+        self.launches.retain(|launch| launch.id != id);
+        if self.launches.len() != count {
+            self.save();
+        }
+    }
+
+    // Launches weighted by how recent they are, like Firefox's frecency. Launches found by
+    // typing the same thing as now count twice.
+    fn frecency(&self, id: &str, query: &str) -> f32 {
+        let now = now();
+        let query = query.trim().to_lowercase();
+        self.launches
+            .iter()
+            .filter(|launch| launch.id == id)
+            .map(|launch| {
+                let days = now.saturating_sub(launch.time) / (24 * 60 * 60);
+                let recency = match days {
+                    0..4 => 1.0,
+                    4..14 => 0.7,
+                    14..31 => 0.5,
+                    _ => 0.3,
+                };
+                let same_query = !query.is_empty()
+                    && !launch.query.is_empty()
+                    && (launch.query.starts_with(&query) || query.starts_with(&launch.query));
+                if same_query { recency * 2.0 } else { recency }
+            })
+            .sum()
+    }
+
+    // Raises a match's score towards 1 by up to half the way. The boost saturates, so an app
+    // launched daily doesn't bury a much better match, and non-matches stay at 0.
+    pub fn rank(&self, score: f32, id: &str, query: &str) -> f32 {
+        if score <= 0.0 || !config::get().launcher.history {
+            return score;
+        }
+        let boost = 1.0 - (-self.frecency(id, query) / 10.0).exp();
+        score + (1.0 - score) * boost * 0.5
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use zbus::proxy;
+use zbus::zvariant::Value;
+
+use super::command;
+use crate::desktop_entry::DesktopEntry;
+use crate::{dbus, systemd};
+
+#[proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+}
+
+// A program to start, with what its desktop entry asks for
+#[derive(Debug, Clone, Default)]
+pub struct Launch {
+    // Names its systemd scope, the desktop file ID without .desktop for apps
+    pub id: String,
+    // The desktop action it runs instead of the app's main Exec line
+    pub action: Option<String>,
+    // Shown when starting it fails
+    pub name: String,
+    pub argv: Vec<String>,
+    // The working directory, Path= in desktop entries
+    pub dir: Option<PathBuf>,
+    // Runs it inside the configured terminal
+    pub terminal: bool,
+    // Asks Mesa to render on the discrete GPU
+    pub non_default_gpu: bool,
+    // Passes it an activation token so it can take focus once its window shows up
+    pub startup_notify: bool,
+}
+
+impl Launch {
+    // An Exec line of the entry or one of its actions, opening `files` if there are any
+    pub fn from_entry(entry: &DesktopEntry, exec: &str, files: &[String]) -> Result<Self, String> {
+        Ok(Self {
+            id: match entry.id.strip_suffix(".desktop") {
+                Some(id) => id.to_string(),
+                None => entry
+                    .path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            },
+            action: None,
+            name: entry.name().unwrap_or_default(),
+            argv: entry.expand_exec(exec, files)?,
+            dir: entry
+                .string("Path")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+            terminal: entry.boolean("Terminal"),
+            non_default_gpu: entry.boolean("PrefersNonDefaultGPU"),
+            startup_notify: entry.boolean("StartupNotify"),
+        })
+    }
+}
+
+// Starts the program in its own session and reaps it on another thread once it exits.
+// Returns its process id.
+pub fn spawn(launch: &Launch, token: Option<&str>) -> std::io::Result<u32> {
+    let argv = if launch.terminal {
+        command::terminal_command(&launch.argv)
+    } else {
+        launch.argv.clone()
+    };
+    let Some((program, args)) = argv.split_first() else {
+        return Err(std::io::Error::other("Nothing to run"));
+    };
+
+    let mut process = Command::new(program);
+    process
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(dir) = &launch.dir {
+        process.current_dir(dir);
+    }
+    if launch.non_default_gpu {
+        process.env("DRI_PRIME", "1");
+    }
+    if let Some(token) = token {
+        // The second one is the X11 startup notification equivalent, for XWayland apps
+        process
+            .env("XDG_ACTIVATION_TOKEN", token)
+            .env("DESKTOP_STARTUP_ID", token);
+    }
+    // Detached from the shell, so restarting it doesn't take the app down too
+    unsafe {
+        process.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    let mut child = process.spawn()?;
+    let pid = child.id();
+    std::thread::spawn(move || child.wait());
+    Ok(pid)
+}
+
+// Moves a started program into its own app-<id>-<random>.scope, following
+// https://systemd.io/DESKTOP_ENVIRONMENTS/, so it's accounted and stopped on its own. Without
+// systemd --user this fails and the program keeps running where it is.
+pub async fn move_to_scope(id: String, pid: u32) -> Result<(), String> {
+    let random = RandomState::new().hash_one(pid);
+    let unit = format!("app-{}-{:016x}.scope", systemd::escape(&id), random);
+    async {
+        let manager = systemd::ManagerProxy::new(dbus::session_bus().await?).await?;
+        manager
+            .start_transient_unit(
+                &unit,
+                "fail",
+                &[
+                    ("PIDs", Value::from(vec![pid])),
+                    ("CollectMode", Value::from("inactive-or-failed")),
+                ],
+                &[],
+            )
+            .await
+    }
+    .await
+    .map(|_| ())
+    .map_err(|error: zbus::Error| error.to_string())
+}
+
+// Tells the user a launch failed, as a notification since the launcher is closed by then
+pub async fn notify_failure(name: String, error: String) -> Result<(), String> {
+    async {
+        let notifications = NotificationsProxy::new(dbus::session_bus().await?).await?;
+        notifications
+            .notify(
+                "my-shell",
+                0,
+                "dialog-error",
+                &format!("Couldn't start {}", name),
+                &error,
+                &[],
+                HashMap::new(),
+                -1,
+            )
+            .await
+    }
+    .await
+    .map(|_| ())
+    .map_err(|error: zbus::Error| error.to_string())
+}
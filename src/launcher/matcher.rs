@@ -0,0 +1,275 @@
+// Fuzzy matching in the style of fzf: the query has to appear in the text as a subsequence,
+// and matches at word starts and runs of consecutive characters score best
+
+// Every matched character
+const SCORE_MATCH: i32 = 16;
+// Skipping characters between two matched ones, the first skipped costs more
+const PENALTY_GAP_START: i32 = 3;
+const PENALTY_GAP_EXTENSION: i32 = 1;
+// Matching the first character of the text, of a word or of a camelCase hump
+const BONUS_FIRST: i32 = 10;
+const BONUS_BOUNDARY: i32 = 8;
+const BONUS_CAMEL: i32 = 7;
+// The least each character matched right after the previous one gets. A run otherwise keeps
+// the bonus of the character it started at, so the rest of a matched word counts like its start.
+const BONUS_CONSECUTIVE: i32 = 4;
+// The first query character's bonus counts this many times, so "vsc" finds Visual Studio Code
+// by its word starts before a mid-word match with fewer gaps
+const BONUS_FIRST_QUERY_CHAR: i32 = 2;
+
+const NONE: i32 = i32::MIN / 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    // Between 0 and 1, 1 being the query as the whole text
+    pub score: f32,
+    // Indices of the matched characters in the text
+    pub positions: Vec<usize>,
+}
+
+fn bonus(text: &[char], i: usize) -> i32 {
+    if i == 0 {
+        return BONUS_FIRST;
+    }
+    let (previous, current) = (text[i - 1], text[i]);
+    if !previous.is_alphanumeric() && current.is_alphanumeric() {
+        BONUS_BOUNDARY
+    } else if previous.is_lowercase() && current.is_uppercase() {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+// What skipping `length` characters costs
+fn gap(length: usize) -> i32 {
+    match length {
+        0 => 0,
+        _ => PENALTY_GAP_START + (length as i32 - 1) * PENALTY_GAP_EXTENSION,
+    }
+}
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+// The best way to match the query in the text, ignoring case. None when it isn't a
+// subsequence of the text, or the query is empty.
+pub fn fuzzy_match(text: &str, query: &str) -> Option<Match> {
+    let original: Vec<char> = text.chars().collect();
+    let text: Vec<char> = original.iter().map(|c| lowercase(*c)).collect();
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(lowercase)
+        .collect();
+    let (n, m) = (text.len(), query.len());
+    if m == 0 || m > n {
+        return None;
+    }
+
+    // Cheap rejection before the quadratic part
+    let mut remaining = text.iter();
+    if !query.iter().all(|q| remaining.any(|c| c == q)) {
+        return None;
+    }
+
+    // matched[i][j]: the best score with query[i] matched at text[j]
+    // gapped[i][j]: the best score with query[i] matched before text[j], skipping up to j
+    // run[i][j]: the bonus of the run of matches ending with query[i] at text[j]
+    // consecutive[i][j]: whether that match directly follows query[i - 1]'s
+    let mut matched = vec![vec![NONE; n]; m];
+    let mut gapped = vec![vec![NONE; n]; m];
+    let mut run = vec![vec![0; n]; m];
+    let mut consecutive = vec![vec![false; n]; m];
+    for i in 0..m {
+        for j in i..n {
+            if text[j] == query[i] {
+                let bonus = bonus(&original, j);
+                if i == 0 {
+                    matched[i][j] = SCORE_MATCH + bonus * BONUS_FIRST_QUERY_CHAR;
+                    run[i][j] = bonus;
+                } else {
+                    let after_gap = gapped[i - 1][j - 1] + SCORE_MATCH + bonus;
+                    let run_bonus = run[i - 1][j - 1].max(bonus);
+                    let after_previous =
+                        matched[i - 1][j - 1] + SCORE_MATCH + run_bonus.max(BONUS_CONSECUTIVE);
+                    if after_previous >= after_gap {
+                        matched[i][j] = after_previous;
+                        run[i][j] = run_bonus;
+                        consecutive[i][j] = true;
+                    } else {
+                        matched[i][j] = after_gap;
+                        run[i][j] = bonus;
+                    }
+                }
+            }
+            if j > 0 {
+                gapped[i][j] = (matched[i][j - 1] - PENALTY_GAP_START)
+                    .max(gapped[i][j - 1] - PENALTY_GAP_EXTENSION);
+            }
+        }
+    }
+
+    let (mut j, best) = matched[m - 1]
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by_key(|(_, score)| *score)?;
+    if best <= NONE / 2 {
+        return None;
+    }
+
+    // Walk back through the choices that led to the best score
+    let mut positions = vec![0; m];
+    for i in (0..m).rev() {
+        positions[i] = j;
+        if i == 0 {
+            break;
+        }
+        j = if consecutive[i][j] {
+            j - 1
+        } else {
+            (0..j - 1)
+                .max_by_key(|k| matched[i - 1][*k] - gap(j - 1 - k))
+                .unwrap_or(j - 1)
+        };
+    }
+
+    // Every character matched as part of a run starting the text, the best it can do
+    let perfect =
+        m as i32 * (SCORE_MATCH + BONUS_FIRST) + BONUS_FIRST * (BONUS_FIRST_QUERY_CHAR - 1);
+    let quality = (best as f32 / perfect as f32).clamp(0.0, 1.0);
+    // Shorter texts win among equally good matches, so "Files" ranks above "Files Backup"
+    let coverage = m as f32 / n as f32;
+    Some(Match {
+        score: quality * (0.8 + 0.2 * coverage),
+        positions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The texts that match, best first
+    fn rank<'a>(query: &str, texts: &[&'a str]) -> Vec<&'a str> {
+        let mut matches: Vec<(&str, f32)> = texts
+            .iter()
+            .filter_map(|text| Some((*text, fuzzy_match(text, query)?.score)))
+            .collect();
+        matches.sort_by(|a, b| b.1.total_cmp(&a.1));
+        matches.into_iter().map(|(text, _)| text).collect()
+    }
+
+    fn positions(text: &str, query: &str) -> Vec<usize> {
+        fuzzy_match(text, query).unwrap().positions
+    }
+
+    #[test]
+    fn needs_a_subsequence() {
+        assert_eq!(fuzzy_match("Firefox", "fz"), None);
+        assert_eq!(fuzzy_match("Firefox", "xof"), None);
+        assert_eq!(fuzzy_match("Fire", "firefox"), None);
+        assert_eq!(fuzzy_match("Firefox", ""), None);
+        assert_eq!(fuzzy_match("Firefox", "  "), None);
+    }
+
+    #[test]
+    fn ignores_case_and_whitespace() {
+        assert_eq!(positions("Firefox", "FIRE"), [0, 1, 2, 3]);
+        assert_eq!(
+            positions("GNOME Terminal", "gnome term"),
+            [0, 1, 2, 3, 4, 6, 7, 8, 9]
+        );
+    }
+
+    #[test]
+    fn scores_between_zero_and_one() {
+        assert_eq!(fuzzy_match("Files", "files").unwrap().score, 1.0);
+        for (text, query) in [("Firefox", "ff"), ("Visual Studio Code", "vsc"), ("x", "x")] {
+            let score = fuzzy_match(text, query).unwrap().score;
+            assert!(score > 0.0 && score <= 1.0, "{} for {}", score, text);
+        }
+    }
+
+    #[test]
+    fn ranks_firefox_for_ff() {
+        let apps = ["LibreOffice", "Diff Viewer", "Firefox", "Files"];
+        assert_eq!(rank("ff", &apps), ["Firefox", "LibreOffice", "Diff Viewer"]);
+        assert_eq!(positions("Firefox", "ff"), [0, 4]);
+    }
+
+    #[test]
+    fn ranks_terminal_for_term() {
+        let apps = ["Determine", "XTerm", "GNOME Terminal", "Terminal"];
+        assert_eq!(
+            rank("term", &apps),
+            ["Terminal", "GNOME Terminal", "XTerm", "Determine"]
+        );
+        assert_eq!(positions("Terminal", "term"), [0, 1, 2, 3]);
+        assert_eq!(positions("GNOME Terminal", "term"), [6, 7, 8, 9]);
+        assert_eq!(positions("Determine", "term"), [2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn word_starts_beat_mid_word_hits() {
+        assert_eq!(
+            rank("fire", &["Spitfire", "Fire Dragon", "Bonfire"])[0],
+            "Fire Dragon"
+        );
+        assert_eq!(
+            rank("set", &["Reset", "Sound Settings"]),
+            ["Sound Settings", "Reset"]
+        );
+        assert_eq!(
+            rank("vsc", &["Avast Scanner", "Visual Studio Code"]),
+            ["Visual Studio Code", "Avast Scanner"]
+        );
+        assert_eq!(positions("Visual Studio Code", "vsc"), [0, 7, 14]);
+        assert_eq!(
+            rank("gimp", &["GNU Image Manipulation Program", "GIMP"]),
+            ["GIMP", "GNU Image Manipulation Program"]
+        );
+        assert_eq!(
+            positions("GNU Image Manipulation Program", "gimp"),
+            [0, 4, 5, 14]
+        );
+    }
+
+    #[test]
+    fn camel_humps_count_as_word_starts() {
+        assert_eq!(
+            rank("sb", &["Disable", "StatusBar"]),
+            ["StatusBar", "Disable"]
+        );
+        assert_eq!(positions("StatusBar", "sb"), [0, 6]);
+    }
+
+    #[test]
+    fn consecutive_runs_beat_scattered_ones() {
+        assert_eq!(rank("abc", &["xaxbxcx", "xabcx"]), ["xabcx", "xaxbxcx"]);
+        assert_eq!(
+            rank("abc", &["A Big Cat", "Abc Def"]),
+            ["Abc Def", "A Big Cat"]
+        );
+        assert_eq!(positions("xabcx", "abc"), [1, 2, 3]);
+    }
+
+    #[test]
+    fn prefers_the_best_occurrence() {
+        // The word start, not the first o in the text
+        assert_eq!(positions("Bookmarks Organizer", "org"), [10, 11, 12]);
+        // The run, not the scattered word starts before it
+        assert_eq!(positions("code decode", "code"), [0, 1, 2, 3]);
+        assert_eq!(positions("c-o-d-e code", "code"), [8, 9, 10, 11]);
+    }
+
+    #[test]
+    fn shorter_texts_win_ties() {
+        assert_eq!(
+            rank("files", &["Files Backup", "Files"]),
+            ["Files", "Files Backup"]
+        );
+    }
+}
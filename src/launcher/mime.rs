@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use super::launch::Launch;
+use super::{apps, glob};
+use crate::config;
+
+#[derive(Debug)]
+struct Glob {
+    weight: u32,
+    mime: String,
+    pattern: String,
+    case_sensitive: bool,
+}
+
+// The first of the data dirs that has the file, e.g. "mime/globs2"
+fn data_file(name: &str) -> Option<PathBuf> {
+    config::data_dirs()
+        .into_iter()
+        .map(|dir| dir.join(name))
+        .find(|path| path.exists())
+}
+
+// shared-mime-info's globs2, lines like "50:image/png:*.png"
+fn parse_globs(contents: &str) -> Vec<Glob> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let weight = fields.next()?.parse().ok()?;
+            let mime = fields.next()?.to_string();
+            let pattern = fields.next()?.to_string();
+            let case_sensitive = fields.next().is_some_and(|flags| flags.contains("cs"));
+            Some(Glob {
+                weight,
+                mime,
+                pattern: if case_sensitive {
+                    pattern
+                } else {
+                    pattern.to_lowercase()
+                },
+                case_sensitive,
+            })
+        })
+        .collect()
+}
+
+static GLOBS: LazyLock<Vec<Glob>> = LazyLock::new(|| {
+    data_file("mime/globs2")
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|contents| parse_globs(&contents))
+        .unwrap_or_default()
+});
+
+// The heaviest and then longest glob matching the file name. A case sensitive match beats
+// an equally good one that ignores case, so main.C is C++ and main.c is C.
+fn lookup<'a>(globs: &'a [Glob], name: &str) -> Option<&'a str> {
+    let lowercase = name.to_lowercase();
+    globs
+        .iter()
+        .filter(|candidate| {
+            let name = if candidate.case_sensitive {
+                name
+            } else {
+                lowercase.as_str()
+            };
+            glob::matches(&candidate.pattern, name)
+        })
+        .max_by_key(|candidate| {
+            (
+                candidate.weight,
+                candidate.pattern.len(),
+                candidate.case_sensitive,
+            )
+        })
+        .map(|candidate| candidate.mime.as_str())
+}
+
+// Lines like "image/png:image-x-generic"
+fn icon_map(name: &str) -> HashMap<String, String> {
+    data_file(name)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(mime, icon)| (mime.to_string(), icon.to_string()))
+        .collect()
+}
+
+static ICONS: LazyLock<HashMap<String, String>> = LazyLock::new(|| icon_map("mime/icons"));
+static GENERIC_ICONS: LazyLock<HashMap<String, String>> =
+    LazyLock::new(|| icon_map("mime/generic-icons"));
+
+// Guessed from the file name, the heaviest and then longest matching glob wins
+pub fn mime_type(path: &Path) -> String {
+    if path.is_dir() {
+        return "inode/directory".to_string();
+    }
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return "application/octet-stream".to_string();
+    };
+    lookup(&GLOBS, name)
+        .unwrap_or("application/octet-stream")
+        .to_string()
+}
+
+// A generic icon is used because every icon theme has those, unlike ones for each type
+pub fn icon(mime: &str) -> String {
+    if mime == "inode/directory" {
+        return "folder".to_string();
+    }
+    if let Some(icon) = ICONS.get(mime).or(GENERIC_ICONS.get(mime)) {
+        return icon.clone();
+    }
+    let media = mime.split('/').next().unwrap_or("application");
+    match media {
+        "audio" | "font" | "image" | "text" | "video" => format!("{}-x-generic", media),
+        _ => "application-x-generic".to_string(),
+    }
+}
+
+// The mimeapps.list files in order of precedence
+fn mimeapps_lists() -> Vec<PathBuf> {
+    let desktops: Vec<String> = std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|desktop| !desktop.is_empty())
+        .map(|desktop| desktop.to_lowercase())
+        .collect();
+
+    let in_dirs = |dirs: Vec<PathBuf>| {
+        dirs.into_iter()
+            .flat_map(|dir| {
+                desktops
+                    .iter()
+                    .map(|desktop| dir.join(format!("{}-mimeapps.list", desktop)))
+                    .chain(std::iter::once(dir.join("mimeapps.list")))
+                    .collect::<Vec<PathBuf>>()
+            })
+            .collect::<Vec<PathBuf>>()
+    };
+
+    let mut lists = in_dirs(config::config_dirs());
+    lists.extend(in_dirs(
+        config::data_dirs()
+            .into_iter()
+            .map(|dir| dir.join("applications"))
+            .collect(),
+    ));
+    lists
+}
+
+// The desktop file ids listed for the mime type in a section of a mimeapps.list
+fn section_entries(contents: &str, section: &str, mime: &str) -> Vec<String> {
+    let mut in_section = false;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_section = line == format!("[{}]", section);
+        } else if in_section {
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim() == mime {
+                    return value
+                        .split(';')
+                        .filter(|id| !id.is_empty())
+                        .map(|id| id.trim().to_string())
+                        .collect();
+                }
+            }
+        }
+    }
+    Vec::new()
+}
+
+// The desktop file ID and file of the default application for a mime type
+pub fn default_application(mime: &str) -> Option<(String, PathBuf)> {
+    let lists: Vec<String> = mimeapps_lists()
+        .into_iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .collect();
+
+    ["Default Applications", "Added Associations"]
+        .iter()
+        .flat_map(|section| {
+            lists
+                .iter()
+                .flat_map(move |contents| section_entries(contents, section, mime))
+        })
+        .find_map(|id| apps::desktop_file(&id).map(|path| (id, path)))
+}
+
+// Opens the file with its default application, or xdg-open without one
+pub fn open(path: &Path) -> Launch {
+    let file = path.to_string_lossy().into_owned();
+    let launch = default_application(&mime_type(path))
+        .and_then(|(id, path)| apps::load(id, &path).ok())
+        .and_then(|entry| Launch::from_entry(&entry, &entry.exec()?, &[file.clone()]).ok());
+
+    launch.unwrap_or(Launch {
+        id: "xdg-open".to_string(),
+        name: "xdg-open".to_string(),
+        argv: vec!["xdg-open".to_string(), file],
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLOBS2: &str = "\
+# This file was automatically generated by the
+# update-mime-database application.
+50:text/plain:*.txt
+50:application/x-troff-man:*.[1-9]
+50:application/gzip:*.gz
+50:application/x-compressed-tar:*.tar.gz
+50:text/x-c++src:*.C:cs
+50:text/x-csrc:*.c
+55:application/x-ms-dos-executable:*.exe
+10:text/x-readme:readme*
+80:text/x-makefile:makefile
+";
+
+    fn mime(name: &str) -> Option<&'static str> {
+        static GLOBS: LazyLock<Vec<Glob>> = LazyLock::new(|| parse_globs(GLOBS2));
+        lookup(&GLOBS, name)
+    }
+
+    #[test]
+    fn parses_globs2() {
+        let globs = parse_globs(GLOBS2);
+        assert_eq!(globs.len(), 9);
+        assert_eq!(globs[0].weight, 50);
+        assert_eq!(globs[0].mime, "text/plain");
+        assert_eq!(globs[0].pattern, "*.txt");
+        assert!(!globs[0].case_sensitive);
+        assert_eq!(globs[4].pattern, "*.C");
+        assert!(globs[4].case_sensitive);
+        // Malformed lines are skipped
+        assert!(parse_globs("x:text/plain:*.txt\n50:text/plain\n").is_empty());
+    }
+
+    #[test]
+    fn looks_up_by_extension() {
+        assert_eq!(mime("notes.txt"), Some("text/plain"));
+        assert_eq!(mime("NOTES.TXT"), Some("text/plain"));
+        assert_eq!(mime("ls.1"), Some("application/x-troff-man"));
+        assert_eq!(mime("photo.jpeg"), None);
+    }
+
+    #[test]
+    fn longer_patterns_win_at_equal_weight() {
+        assert_eq!(mime("archive.tar.gz"), Some("application/x-compressed-tar"));
+        assert_eq!(mime("notes.gz"), Some("application/gzip"));
+    }
+
+    #[test]
+    fn heavier_patterns_win() {
+        assert_eq!(mime("Makefile"), Some("text/x-makefile"));
+        assert_eq!(mime("readme.txt"), Some("text/plain"));
+        assert_eq!(mime("readme"), Some("text/x-readme"));
+    }
+
+    #[test]
+    fn respects_case_sensitive_globs() {
+        assert_eq!(mime("main.C"), Some("text/x-c++src"));
+        assert_eq!(mime("main.c"), Some("text/x-csrc"));
+    }
+
+    #[test]
+    fn picks_generic_icons() {
+        assert_eq!(icon("inode/directory"), "folder");
+        assert_eq!(icon("video/x-nonexistent-test"), "video-x-generic");
+        assert_eq!(
+            icon("application/x-nonexistent-test"),
+            "application-x-generic"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use hyprland::shared::Address;
+
+use super::launch::Launch;
+
+// What happens when a launcher item is submitted
+#[derive(Debug, Clone)]
+pub enum Action {
+    // An app to start, from its desktop entry
+    Launch(Launch),
+    // Text put on the clipboard
+    Copy(String),
+    // A shell command typed by the user, run through their shell or in a terminal
+    Command(String),
+    // A file or directory, opened with the default application for its type
+    Open(PathBuf),
+    // A Hyprland window to focus, Shift+Delete closes it instead
+    Focus(Address),
+}
+
+#[derive(Debug, Clone)]
+pub struct LauncherItem {
+    pub title: String,
+    // Indices of the title's characters the query matched, shown in bold
+    pub highlights: Vec<usize>,
+    pub subtitle: Option<String>,
+    // An icon name from the icon theme
+    pub icon: Option<String>,
+    pub action: Action,
+    // Between 0 and 1, so items from different providers can be ranked in one list
+    pub score: f32,
+}
+
+// A source of launcher items, e.g. installed apps or open windows
+pub trait SearchProvider: std::fmt::Debug {
+    // Input starting with this is searched by this provider alone, without the prefix
+    fn prefix(&self) -> Option<String> {
+        None
+    }
+
+    // Called whenever the launcher opens, so providers can reload what they search
+    fn refresh(&mut self) {}
+
+    // Items matching the query, in any order. Items with a score of 0 are dropped.
+    fn search(&self, query: &str) -> Vec<LauncherItem>;
+
+    // Items a provider with a prefix adds to searches without one
+    fn search_unprefixed(&self, _query: &str) -> Vec<LauncherItem> {
+        Vec::new()
+    }
+
+    // Called with every submitted item and the input it was found with, whichever provider it
+    // came from
+    fn activated(&mut self, _item: &LauncherItem, _query: &str) {}
+
+    // Called on Shift+Delete, to drop what's remembered about an item
+    fn forget(&mut self, _item: &LauncherItem) {}
+}
+
+// Merges the items of every provider, best first. When the query starts with a provider's
+// prefix only that provider is searched, otherwise all of them are.
+pub fn search(providers: &[Box<dyn SearchProvider>], query: &str) -> Vec<LauncherItem> {
+    let prefixed: Vec<(&Box<dyn SearchProvider>, &str)> = providers
+        .iter()
+        .filter_map(|provider| {
+            let prefix = provider.prefix()?;
+            if prefix.is_empty() {
+                return None;
+            }
+            Some((provider, query.strip_prefix(prefix.as_str())?))
+        })
+        .collect();
+
+    let mut items: Vec<LauncherItem> = if prefixed.is_empty() {
+        providers
+            .iter()
+            .flat_map(|provider| match provider.prefix() {
+                Some(_) => provider.search_unprefixed(query),
+                None => provider.search(query),
+            })
+            .collect()
+    } else {
+        prefixed
+            .into_iter()
+            .flat_map(|(provider, query)| provider.search(query))
+            .collect()
+    };
+    items.retain(|item| item.score > 0.0);
+    // Stable, so providers earlier in the list win ties
+    items.sort_by(|a, b| b.score.total_cmp(&a.score));
+    items
+}
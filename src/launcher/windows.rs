@@ -0,0 +1,92 @@
+use hyprland::data::{Client, Clients};
+use hyprland::shared::HyprData;
+
+use super::matcher::fuzzy_match;
+use super::provider::{Action, LauncherItem, SearchProvider};
+use crate::config;
+
+// Mapped windows, most recently focused first
+fn clients() -> Vec<Client> {
+    let mut clients: Vec<Client> = match Clients::get() {
+        Ok(clients) => clients.to_vec(),
+        Err(error) => {
+            dbg!(error);
+            Vec::new()
+        }
+    };
+    clients.retain(|client| client.mapped);
+    clients.sort_by_key(|client| client.focus_history_id);
+    clients
+}
+
+fn item(client: &Client, title: String, highlights: Vec<usize>, score: f32) -> LauncherItem {
+    LauncherItem {
+        title,
+        highlights,
+        subtitle: Some(format!(
+            "{} on workspace {}, Shift+Delete closes it",
+            client.class, client.workspace.name
+        )),
+        // Most apps use their class as their icon name
+        icon: Some(client.class.to_lowercase()),
+        action: Action::Focus(client.address.clone()),
+        score,
+    }
+}
+
+// Open windows, queried on every search since they change while the launcher is open
+#[derive(Debug, Default)]
+pub struct Windows {}
+
+impl SearchProvider for Windows {
+    fn prefix(&self) -> Option<String> {
+        Some(config::get().launcher.window_prefix.clone())
+    }
+
+    fn search(&self, query: &str) -> Vec<LauncherItem> {
+        let query = query.trim();
+        clients()
+            .iter()
+            .enumerate()
+            .map(|(i, client)| {
+                if query.is_empty() {
+                    // Everything, keeping the focus order
+                    return item(
+                        client,
+                        client.title.clone(),
+                        Vec::new(),
+                        1.0 - i as f32 * 0.001,
+                    );
+                }
+                let (score, highlights) = match fuzzy_match(&client.title, query) {
+                    Some(found) => (found.score, found.positions),
+                    None => (0.0, Vec::new()),
+                };
+                let class = fuzzy_match(&client.class, query).map_or(0.0, |found| found.score);
+                // Highlighting only the title, which is what's shown
+                if class > score {
+                    return item(client, client.title.clone(), Vec::new(), class);
+                }
+                item(client, client.title.clone(), highlights, score)
+            })
+            .collect()
+    }
+
+    // Scored on the class alone, like apps are on their name, so a running app's window ends
+    // up next to its launch entry
+    fn search_unprefixed(&self, query: &str) -> Vec<LauncherItem> {
+        let query = query.trim();
+        clients()
+            .iter()
+            .map(|client| {
+                let score = fuzzy_match(&client.class, query).map_or(0.0, |found| found.score);
+                item(
+                    client,
+                    format!("Switch to {}", client.title),
+                    Vec::new(),
+                    score,
+                )
+            })
+            .collect()
+    }
+}
@@ -6,14 +6,23 @@ use cosmic::widget::Space;
 use iced::window::Id;
 use iced::{Element, Task};
 use launcher::Launcher;
+use power::PowerMenu;
 use window::Window;
 
 mod bar;
+mod config;
+mod dbus;
+mod desktop_entry;
+mod icon_theme;
 mod launcher;
+mod logind;
+mod power;
+mod systemd;
 mod window;
 
 pub struct Shell {
     launcher: launcher::Launcher,
+    power: power::PowerMenu,
     bar: bar::Bar,
 }
 
@@ -23,22 +32,27 @@ pub enum ShellMessage {
     ShortcutError(String),
     ShortcutActivated(String),
     ShortcutsSetup,
+    ConfigChanged,
     LauncherMessage(launcher::Message),
+    PowerMessage(power::Message),
     BarMessage(bar::Message),
 }
 
 impl Shell {
     pub fn new() -> (Self, Task<ShellMessage>) {
         let (launcher_window, launcher_init_task) = Launcher::new();
+        let (power_window, power_init_task) = PowerMenu::new();
         let (bar_window, bar_init_task) = Bar::new();
 
         (
             Self {
                 bar: bar_window,
                 launcher: launcher_window,
+                power: power_window,
             },
             Task::batch(vec![
                 launcher_init_task.map(|e| ShellMessage::LauncherMessage(e)),
+                power_init_task.map(|e| ShellMessage::PowerMessage(e)),
                 bar_init_task.map(|e| ShellMessage::BarMessage(e)),
             ]),
         )
@@ -55,6 +69,12 @@ impl Shell {
                 .view()
                 .map(|e| ShellMessage::LauncherMessage(e));
         }
+        if self.power.window.is_some_and(|window_id| window_id == id) {
+            return self.power.view().map(|e| ShellMessage::PowerMessage(e));
+        }
+        if let Some(popup) = self.bar.popup_view(id) {
+            return popup.map(|e| ShellMessage::BarMessage(e));
+        }
         if id == self.bar.id {
             return self.bar.view().map(|e| ShellMessage::BarMessage(e));
         } else {
@@ -70,6 +90,9 @@ impl Shell {
                     "ToggleLauncher" => {
                         return Task::done(LauncherMessage(launcher::Message::Open));
                     }
+                    "TogglePowerMenu" => {
+                        return Task::done(PowerMessage(power::Message::Open));
+                    }
                     _ => println!("Shouldn't happen! Shortcut ID {} is not handled!", thing), // TODO: Enums and hashmaps? We'll see!
                 }
                 Task::none()
@@ -82,6 +105,18 @@ impl Shell {
                 dbg!("Shortcuts Setup");
                 Task::none()
             }
+            ConfigChanged => {
+                config::reload();
+                Task::batch(vec![
+                    Task::done(BarMessage(bar::Message::Custom(
+                        bar::custom::Message::ConfigReloaded,
+                    ))),
+                    // Picks the configured devices again, which also moves the brightness watch
+                    Task::done(BarMessage(bar::Message::Brightness(
+                        bar::brightness::Message::Changed,
+                    ))),
+                ])
+            }
             LauncherMessage(message) => {
                 if let launcher::Message::ShellMessage(shell_message) = message {
                     self.update(dbg!(*shell_message.clone()))
@@ -92,6 +127,15 @@ impl Shell {
                         .map(|e| ShellMessage::LauncherMessage(e))
                 }
             }
+            PowerMessage(message) => {
+                if let power::Message::ShellMessage(shell_message) = message {
+                    self.update(dbg!(*shell_message.clone()))
+                } else {
+                    self.power
+                        .update(message)
+                        .map(|e| ShellMessage::PowerMessage(e))
+                }
+            }
             BarMessage(message) => {
                 if let bar::Message::ShellMessage(shell_message) = message {
                     self.update(dbg!(*shell_message.clone()))
@@ -111,10 +155,14 @@ impl Shell {
                 .launcher
                 .subscription()
                 .map(|message| ShellMessage::LauncherMessage(message)),
+            self.power
+                .subscription()
+                .map(|message| ShellMessage::PowerMessage(message)),
             self
                 .bar
                 .subscription()
                 .map(|message| ShellMessage::BarMessage(message)),
+            config::subscription().map(|_| ShellMessage::ConfigChanged),
                 
             Subscription::run(|| {
                 iced::stream::channel(10, async |mut output| {
@@ -135,10 +183,16 @@ impl Shell {
                         }
                     };
 
-                    let shortcuts = vec![ashpd::desktop::global_shortcuts::NewShortcut::new(
-                        "ToggleLauncher",
-                        "Toggles the Application Launcher menu",
-                    )];
+                    let shortcuts = vec![
+                        ashpd::desktop::global_shortcuts::NewShortcut::new(
+                            "ToggleLauncher",
+                            "Toggles the Application Launcher menu",
+                        ),
+                        ashpd::desktop::global_shortcuts::NewShortcut::new(
+                            "TogglePowerMenu",
+                            "Toggles the Power menu",
+                        ),
+                    ];
 
                     let _ = proxy.bind_shortcuts(&session, &shortcuts, None).await;
 
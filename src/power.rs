@@ -0,0 +1,545 @@
+use cosmic::cctk;
+use cosmic::cctk::sctk::shell::wlr_layer::Anchor;
+use cosmic::iced::event::Status;
+use cosmic::iced::keyboard::Key;
+use cosmic::iced::keyboard::key::Named;
+use cosmic::iced::{self, Alignment, Event, event, keyboard, mouse};
+use cosmic::iced_runtime::platform_specific::wayland::layer_surface::{
+    IcedOutput, SctkLayerSurfaceSettings,
+};
+use cosmic::iced_widget::{column, row};
+use cosmic::iced_winit::commands::layer_surface::{destroy_layer_surface, get_layer_surface};
+use cosmic::iced_winit::commands::subsurface::KeyboardInteractivity;
+use hyprland::dispatch;
+use hyprland::dispatch::Dispatch;
+use hyprland::dispatch::DispatchType;
+use iced::border::radius;
+use iced::widget::{container, mouse_area, text};
+use iced::{Border, Element, Length, Task, Theme, window};
+use serde::Deserialize;
+use zbus::Connection;
+
+use crate::window::Window;
+use crate::{ShellMessage, config, dbus, logind};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Ask before anything that ends the session
+    pub confirm: bool,
+    // Run this through Hyprland instead of asking logind to lock the session (e.g. "hyprlock")
+    pub lock_command: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            confirm: true,
+            lock_command: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Lock,
+    Logout,
+    Suspend,
+    Hibernate,
+    Reboot,
+    Shutdown,
+}
+
+impl Action {
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Lock => "Lock",
+            Action::Logout => "Log Out",
+            Action::Suspend => "Suspend",
+            Action::Hibernate => "Hibernate",
+            Action::Reboot => "Reboot",
+            Action::Shutdown => "Shut Down",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Action::Lock => "🔒",
+            Action::Logout => "⇥",
+            Action::Suspend => "⏾",
+            Action::Hibernate => "❄",
+            Action::Reboot => "⟳",
+            Action::Shutdown => "⏻",
+        }
+    }
+
+    // Locking and suspending are easy to undo, everything else loses the session
+    pub fn needs_confirmation(self) -> bool {
+        matches!(
+            self,
+            Action::Logout | Action::Hibernate | Action::Reboot | Action::Shutdown
+        )
+    }
+}
+
+fn is_allowed(answer: zbus::Result<String>) -> bool {
+    matches!(answer.as_deref(), Ok("yes") | Ok("challenge"))
+}
+
+// Lock and logout don't go through the Manager, so they're always offered
+pub async fn available_actions(connection: &Connection) -> zbus::Result<Vec<Action>> {
+    let manager = logind::ManagerProxy::new(connection).await?;
+
+    let mut actions = vec![Action::Lock, Action::Logout];
+    if is_allowed(manager.can_suspend().await) {
+        actions.push(Action::Suspend);
+    }
+    if is_allowed(manager.can_hibernate().await) {
+        actions.push(Action::Hibernate);
+    }
+    if is_allowed(manager.can_reboot().await) {
+        actions.push(Action::Reboot);
+    }
+    if is_allowed(manager.can_power_off().await) {
+        actions.push(Action::Shutdown);
+    }
+    Ok(actions)
+}
+
+pub async fn perform(connection: &Connection, action: Action) -> zbus::Result<()> {
+    match action {
+        Action::Lock => logind::SessionProxy::new(connection).await?.lock().await,
+        Action::Logout => Dispatch::call_async(DispatchType::Exit)
+            .await
+            .map_err(|error| zbus::Error::Failure(error.to_string())),
+        Action::Suspend => {
+            logind::ManagerProxy::new(connection)
+                .await?
+                .suspend(true)
+                .await
+        }
+        Action::Hibernate => {
+            logind::ManagerProxy::new(connection)
+                .await?
+                .hibernate(true)
+                .await
+        }
+        Action::Reboot => {
+            logind::ManagerProxy::new(connection)
+                .await?
+                .reboot(true)
+                .await
+        }
+        Action::Shutdown => {
+            logind::ManagerProxy::new(connection)
+                .await?
+                .power_off(true)
+                .await
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PowerMenu {
+    pub window: Option<window::Id>,
+    pub actions: Vec<Action>,
+    pub selected_item: usize,
+    // The action waiting for the user to confirm it
+    pub confirming: Option<Action>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Open,
+    Close,
+    Available(Result<Vec<Action>, String>),
+    SelectionLeft,
+    SelectionRight,
+    Submit,
+    Activate(Action),
+    Confirm,
+    Cancel,
+    Performed(Result<(), String>),
+    ShellMessage(Box<ShellMessage>),
+}
+
+impl PowerMenu {
+    fn item<'a>(
+        icon: &'a str,
+        label: &'a str,
+        selected: bool,
+        message: Message,
+    ) -> Element<'a, Message> {
+        mouse_area(
+            container(
+                column![text(icon).size(48), text(label).size(18)]
+                    .spacing(10)
+                    .align_x(Alignment::Center),
+            )
+            .center(Length::Fixed(140.0))
+            .style(move |theme: &Theme| container::Style {
+                border: Border {
+                    radius: radius(20),
+                    width: 0.0,
+                    ..Default::default()
+                },
+                background: Some(if selected {
+                    theme.extended_palette().primary.base.color.into()
+                } else {
+                    theme.extended_palette().background.weak.color.into()
+                }),
+                ..Default::default()
+            }),
+        )
+        .on_press(message)
+        .into()
+    }
+}
+
+impl Window for PowerMenu {
+    type Message = Message;
+
+    fn new() -> (Self, Task<Self::Message>) {
+        (
+            Self {
+                window: None,
+                actions: Vec::new(),
+                selected_item: 0,
+                confirming: None,
+            },
+            Task::none(),
+        )
+    }
+
+    fn view(self: &Self) -> Element<'_, Self::Message> {
+        let content: Element<'_, Self::Message> = match self.confirming {
+            Some(action) => column![
+                text(format!("{}?", action.label())).size(30),
+                row![
+                    Self::item("✕", "Cancel", self.selected_item == 0, Message::Cancel),
+                    Self::item(
+                        action.icon(),
+                        action.label(),
+                        self.selected_item == 1,
+                        Message::Confirm
+                    ),
+                ]
+                .spacing(20),
+            ]
+            .spacing(20)
+            .align_x(Alignment::Center)
+            .into(),
+            None => row(self.actions.iter().enumerate().map(|(i, action)| {
+                Self::item(
+                    action.icon(),
+                    action.label(),
+                    i == self.selected_item,
+                    Message::Activate(*action),
+                )
+            }))
+            .spacing(20)
+            .into(),
+        };
+
+        container(content)
+            .center(Length::Fill)
+            .style(|theme: &Theme| container::Style {
+                background: Some(
+                    theme
+                        .extended_palette()
+                        .background
+                        .strong
+                        .color
+                        .scale_alpha(0.25)
+                        .into(),
+                ),
+                ..Default::default()
+            })
+            .into()
+    }
+
+    fn update(self: &mut Self, message: Self::Message) -> Task<Self::Message> {
+        use Message::*;
+        return match message {
+            Open => {
+                if self.window.is_some() {
+                    return Task::done(Close);
+                }
+
+                let id = window::Id::unique();
+                let layer_shell_task = get_layer_surface(SctkLayerSurfaceSettings {
+                    id,
+                    layer: cctk::sctk::shell::wlr_layer::Layer::Top,
+                    output: IcedOutput::Active,
+                    keyboard_interactivity: KeyboardInteractivity::Exclusive,
+                    pointer_interactivity: true,
+                    anchor: Anchor::all(),
+                    exclusive_zone: -1,
+                    ..Default::default()
+                });
+                self.window = Some(id);
+                self.actions = vec![Action::Lock, Action::Logout];
+                self.selected_item = 0;
+                self.confirming = None;
+
+                Task::batch(vec![
+                    layer_shell_task,
+                    Task::perform(
+                        async { available_actions(dbus::system_bus().await?).await },
+                        |result| Available(result.map_err(|error| error.to_string())),
+                    ),
+                ])
+            }
+            Close => {
+                if let Some(id) = self.window {
+                    self.window = None;
+                    return destroy_layer_surface(id);
+                }
+                Task::none()
+            }
+            Available(Ok(actions)) => {
+                self.actions = actions;
+                self.selected_item = self.selected_item.min(self.actions.len() - 1);
+                Task::none()
+            }
+            Available(Err(error)) => {
+                dbg!(error);
+                Task::none()
+            }
+            SelectionLeft => {
+                if self.window.is_none() {
+                    return Task::none();
+                }
+                self.selected_item = self.selected_item.saturating_sub(1);
+                Task::none()
+            }
+            SelectionRight => {
+                if self.window.is_none() {
+                    return Task::none();
+                }
+                let count = if self.confirming.is_some() {
+                    2
+                } else {
+                    self.actions.len()
+                };
+                self.selected_item = (self.selected_item + 1).min(count - 1);
+                Task::none()
+            }
+            Submit => {
+                if self.window.is_none() {
+                    return Task::none();
+                }
+                match self.confirming {
+                    Some(_) if self.selected_item == 1 => Task::done(Confirm),
+                    Some(_) => Task::done(Cancel),
+                    None => match self.actions.get(self.selected_item) {
+                        Some(action) => Task::done(Activate(*action)),
+                        None => Task::none(),
+                    },
+                }
+            }
+            Activate(action) => {
+                if action.needs_confirmation() && config::get().power.confirm {
+                    self.confirming = Some(action);
+                    // Default to cancelling, so a stray Enter doesn't reboot
+                    self.selected_item = 0;
+                    return Task::none();
+                }
+                self.confirming = Some(action);
+                Task::done(Confirm)
+            }
+            Confirm => {
+                let Some(action) = self.confirming.take() else {
+                    return Task::none();
+                };
+
+                if action == Action::Lock {
+                    if let Some(command) = &config::get().power.lock_command {
+                        let _ = dispatch!(Exec, command);
+                        return Task::done(Close);
+                    }
+                }
+
+                Task::batch(vec![
+                    Task::done(Close),
+                    Task::perform(
+                        async move { perform(dbus::system_bus().await?, action).await },
+                        |result| Performed(result.map_err(|error| error.to_string())),
+                    ),
+                ])
+            }
+            Cancel => {
+                if self.confirming.is_none() {
+                    return Task::done(Close);
+                }
+                let action = self.confirming.take();
+                self.selected_item = self
+                    .actions
+                    .iter()
+                    .position(|a| Some(*a) == action)
+                    .unwrap_or(0);
+                Task::none()
+            }
+            Performed(Ok(())) => Task::none(),
+            Performed(Err(error)) => {
+                dbg!(error);
+                Task::none()
+            }
+            ShellMessage(_) => Task::none(),
+        };
+    }
+
+    fn subscription(self: &Self) -> iced::Subscription<Self::Message> {
+        event::listen_with(|event, status, _id: window::Id| match event {
+            Event::Keyboard(keyboard::Event::KeyPressed { key, .. }) => match key {
+                Key::Named(Named::Escape) => Some(Message::Cancel),
+                Key::Named(Named::ArrowLeft) => Some(Message::SelectionLeft),
+                Key::Named(Named::ArrowRight) => Some(Message::SelectionRight),
+                Key::Named(Named::Enter) => Some(Message::Submit),
+                _ => None,
+            },
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                if status == Status::Ignored =>
+            {
+                Some(Message::Close)
+            }
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    // Stands in for logind's Manager, answering the Can* methods from a table and recording
+    // what's called
+    struct MockManager {
+        answers: HashMap<&'static str, &'static str>,
+        calls: Arc<Mutex<Vec<(&'static str, bool)>>>,
+    }
+
+    impl MockManager {
+        fn answer(&self, method: &str) -> zbus::fdo::Result<String> {
+            self.answers
+                .get(method)
+                .map(|answer| answer.to_string())
+                .ok_or_else(|| zbus::fdo::Error::NotSupported(method.to_string()))
+        }
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl MockManager {
+        fn can_suspend(&self) -> zbus::fdo::Result<String> {
+            self.answer("CanSuspend")
+        }
+        fn can_hibernate(&self) -> zbus::fdo::Result<String> {
+            self.answer("CanHibernate")
+        }
+        fn can_reboot(&self) -> zbus::fdo::Result<String> {
+            self.answer("CanReboot")
+        }
+        fn can_power_off(&self) -> zbus::fdo::Result<String> {
+            self.answer("CanPowerOff")
+        }
+        fn suspend(&self, interactive: bool) {
+            self.calls.lock().unwrap().push(("Suspend", interactive));
+        }
+        fn power_off(&self, interactive: bool) {
+            self.calls.lock().unwrap().push(("PowerOff", interactive));
+        }
+    }
+
+    // A client connection talking straight to the mock, without a bus in between
+    async fn mock_logind(manager: MockManager) -> (Connection, Connection) {
+        let (server, client) = tokio::net::UnixStream::pair().unwrap();
+        let server = zbus::connection::Builder::unix_stream(server)
+            .server(zbus::Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at("/org/freedesktop/login1", manager)
+            .unwrap()
+            .build();
+        let client = zbus::connection::Builder::unix_stream(client).p2p().build();
+        let (server, client) = tokio::try_join!(server, client).unwrap();
+        (server, client)
+    }
+
+    fn manager(answers: &[(&'static str, &'static str)]) -> MockManager {
+        MockManager {
+            answers: answers.iter().copied().collect(),
+            calls: Arc::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn offers_everything_logind_allows() {
+        let (_server, client) = mock_logind(manager(&[
+            ("CanSuspend", "yes"),
+            ("CanHibernate", "yes"),
+            ("CanReboot", "yes"),
+            ("CanPowerOff", "yes"),
+        ]))
+        .await;
+        assert_eq!(
+            available_actions(&client).await.unwrap(),
+            [
+                Action::Lock,
+                Action::Logout,
+                Action::Suspend,
+                Action::Hibernate,
+                Action::Reboot,
+                Action::Shutdown,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn hides_what_logind_refuses() {
+        let (_server, client) = mock_logind(manager(&[
+            ("CanSuspend", "challenge"),
+            ("CanHibernate", "na"),
+            ("CanReboot", "no"),
+            ("CanPowerOff", "challenge"),
+        ]))
+        .await;
+        assert_eq!(
+            available_actions(&client).await.unwrap(),
+            [
+                Action::Lock,
+                Action::Logout,
+                Action::Suspend,
+                Action::Shutdown,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn hides_actions_logind_fails_to_answer() {
+        let (_server, client) = mock_logind(manager(&[("CanReboot", "yes")])).await;
+        assert_eq!(
+            available_actions(&client).await.unwrap(),
+            [Action::Lock, Action::Logout, Action::Reboot]
+        );
+    }
+
+    #[tokio::test]
+    async fn performs_actions_interactively() {
+        let manager = manager(&[]);
+        let calls = manager.calls.clone();
+        let (_server, client) = mock_logind(manager).await;
+
+        perform(&client, Action::Suspend).await.unwrap();
+        perform(&client, Action::Shutdown).await.unwrap();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [("Suspend", true), ("PowerOff", true)]
+        );
+
+        // Not served by the mock
+        assert!(perform(&client, Action::Reboot).await.is_err());
+    }
+}
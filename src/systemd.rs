@@ -0,0 +1,34 @@
+use zbus::proxy;
+use zbus::zvariant::{OwnedObjectPath, Value};
+
+// The user's service manager, on the session bus
+#[proxy(
+    interface = "org.freedesktop.systemd1.Manager",
+    default_service = "org.freedesktop.systemd1",
+    default_path = "/org/freedesktop/systemd1"
+)]
+pub trait Manager {
+    // Creates a unit that lives until its processes exit. Mode is how conflicts with queued
+    // jobs are handled, e.g. "fail".
+    fn start_transient_unit(
+        &self,
+        name: &str,
+        mode: &str,
+        properties: &[(&str, Value<'_>)],
+        aux: &[(&str, &[(&str, Value<'_>)])],
+    ) -> zbus::Result<OwnedObjectPath>;
+}
+
+// Escapes a string for use in a unit name like systemd-escape does, including dashes since
+// they separate the parts of app unit names
+pub fn escape(name: &str) -> String {
+    name.bytes()
+        .enumerate()
+        .map(|(i, byte)| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b':' | b'_' => (byte as char).to_string(),
+            // A leading dot would make a hidden unit
+            b'.' if i > 0 => ".".to_string(),
+            _ => format!("\\x{:02x}", byte),
+        })
+        .collect()
+}